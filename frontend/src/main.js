@@ -42,6 +42,26 @@ function initFancySelects() {
   }
 }
 
+function initMultiSelects() {
+  // Including and excluding the same value at once can't match anything.
+  for (const option of document.querySelectorAll(".multi-select-option")) {
+    const checkboxes = [...option.querySelectorAll("input[type=checkbox]")];
+    for (const checkbox of checkboxes) {
+      checkbox.addEventListener("change", () => {
+        if (!checkbox.checked) {
+          return;
+        }
+
+        for (const other of checkboxes) {
+          if (other != checkbox) {
+            other.checked = false;
+          }
+        }
+      });
+    }
+  }
+}
+
 function initThumbnailLoaders() {
   for (const loader of document.querySelectorAll(".thumbnail-loader")) {
     const img = loader.querySelector(".image-container img");
@@ -62,11 +82,41 @@ function initThumbnailLoaders() {
   }
 }
 
+function initMatchCount() {
+  const matchCount = document.querySelector("#match-count");
+  if (!matchCount) {
+    return;
+  }
+
+  const form = matchCount.closest("form");
+  const updateMatchCount = async () => {
+    const query = new URLSearchParams(new FormData(form));
+    const response = await fetch(`${matchCount.dataset.countUrl}?${query}`);
+    if (!response.ok) {
+      return;
+    }
+
+    const { count } = await response.json();
+    matchCount.innerText = `${count} level${count == 1 ? "" : "s"} match these filters.`;
+  };
+
+  // The fancy select buttons dispatch their own change events, which don't
+  // bubble up to the form.
+  for (const select of form.querySelectorAll("select")) {
+    select.addEventListener("change", updateMatchCount);
+  }
+  for (const checkbox of form.querySelectorAll(".multi-select input")) {
+    checkbox.addEventListener("change", updateMatchCount);
+  }
+}
+
 const currentUrl = new URL(window.location);
 if (currentUrl.pathname == "/smm2/random_level/") {
   document.addEventListener("DOMContentLoaded", () => {
     initClickCopy();
     initFancySelects();
+    initMatchCount();
+    initMultiSelects();
     initThumbnailLoaders();
   });
 }
@@ -0,0 +1,17 @@
+CREATE TABLE import_runs (
+  "id" BIGSERIAL PRIMARY KEY NOT NULL,
+  "game" TEXT NOT NULL,
+  "source" TEXT NOT NULL,
+  "started_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  "finished_at" TIMESTAMP WITH TIME ZONE,
+  "outcome" TEXT,
+
+  "added" BIGINT NOT NULL DEFAULT 0,
+  "updated" BIGINT NOT NULL DEFAULT 0,
+  "removed" BIGINT NOT NULL DEFAULT 0,
+  "unchanged" BIGINT NOT NULL DEFAULT 0,
+  "skipped_blocklisted" BIGINT NOT NULL DEFAULT 0,
+
+  "error" TEXT
+);
+CREATE INDEX import_runs_started_at_idx ON import_runs ("started_at");
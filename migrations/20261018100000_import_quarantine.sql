@@ -0,0 +1,12 @@
+CREATE TABLE import_quarantine (
+  "id" BIGSERIAL PRIMARY KEY NOT NULL,
+  "import_run_id" BIGINT NOT NULL REFERENCES import_runs ("id") ON DELETE CASCADE,
+  "level_id" TEXT,
+  "raw" JSONB NOT NULL,
+  "error" TEXT NOT NULL
+);
+CREATE INDEX import_quarantine_import_run_id_idx ON import_quarantine ("import_run_id");
+CREATE INDEX import_quarantine_level_id_idx ON import_quarantine ("level_id");
+
+ALTER TABLE import_runs
+  ADD "quarantined" BIGINT NOT NULL DEFAULT 0;
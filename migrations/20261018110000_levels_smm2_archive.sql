@@ -0,0 +1,31 @@
+ALTER TABLE levels_smm2
+  ADD "first_seen_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
+
+CREATE TABLE levels_smm2_archive (
+  "archive_id" BIGSERIAL PRIMARY KEY NOT NULL,
+  "id" TEXT NOT NULL,
+  "year" BIGINT NOT NULL,
+
+  "title" TEXT NOT NULL,
+  "description" TEXT,
+  "uploaded_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "clearcheck_ms" BIGINT NOT NULL,
+
+  "attempts" BIGINT NOT NULL,
+  "footprints" BIGINT NOT NULL,
+  "likes" BIGINT NOT NULL,
+  "boos" BIGINT NOT NULL,
+  "comments" BIGINT NOT NULL,
+
+  "clear_condition" BIGINT,
+  "clear_condition_magnitude" BIGINT,
+  "style" TEXT NOT NULL,
+  "theme" TEXT NOT NULL,
+  "tags" TEXT[] NOT NULL,
+
+  "first_seen_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "removed_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  "removal_reason" TEXT NOT NULL
+);
+CREATE INDEX levels_smm2_archive_id_idx ON levels_smm2_archive ("id");
+CREATE INDEX levels_smm2_archive_removed_at_idx ON levels_smm2_archive ("removed_at");
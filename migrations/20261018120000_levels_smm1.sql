@@ -0,0 +1,50 @@
+CREATE TABLE levels_smm1 (
+  "id" TEXT PRIMARY KEY NOT NULL,
+  "year" BIGINT NOT NULL,
+
+  "title" TEXT NOT NULL,
+  "uploaded_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  "attempts" BIGINT NOT NULL,
+  "footprints" BIGINT NOT NULL,
+  "likes" BIGINT NOT NULL,
+
+  "style" TEXT NOT NULL,
+
+  "first_seen_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+CREATE INDEX levels_smm1_year_idx ON levels_smm1 ("year");
+CREATE INDEX levels_smm1_attempts_idx ON levels_smm1 ("attempts");
+CREATE INDEX levels_smm1_style_idx ON levels_smm1 ("style");
+
+CREATE TABLE levels_smm1_archive (
+  "archive_id" BIGSERIAL PRIMARY KEY NOT NULL,
+  "id" TEXT NOT NULL,
+  "year" BIGINT NOT NULL,
+
+  "title" TEXT NOT NULL,
+  "uploaded_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  "attempts" BIGINT NOT NULL,
+  "footprints" BIGINT NOT NULL,
+  "likes" BIGINT NOT NULL,
+
+  "style" TEXT NOT NULL,
+
+  "first_seen_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "removed_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  "removal_reason" TEXT NOT NULL
+);
+CREATE INDEX levels_smm1_archive_id_idx ON levels_smm1_archive ("id");
+CREATE INDEX levels_smm1_archive_removed_at_idx ON levels_smm1_archive ("removed_at");
+
+INSERT INTO "public"."levels_smm1" ("id", "year", "title", "uploaded_at", "attempts", "footprints", "likes", "style") VALUES
+  ('0000000000000001', 2015, 'Placeholder: Castle Crawl', '2015-09-14 10:12:00+00', 412, 37, 2, 'SMB1'),
+  ('0000000000000002', 2015, 'Placeholder: Airship Assault', '2015-11-02 18:40:00+00', 1203, 95, 8, 'SMB3'),
+  ('0000000000000003', 2016, 'Placeholder: Ghost House Maze', '2016-03-21 07:05:00+00', 87, 12, 0, 'SMW'),
+  ('0000000000000004', 2016, 'Placeholder: Shell Jump Gauntlet', '2016-07-30 21:17:00+00', 2510, 140, 14, 'NSMBU'),
+  ('0000000000000005', 2017, 'Placeholder: Underground Speedrun', '2017-01-11 13:52:00+00', 54, 9, 1, 'SMB1'),
+  ('0000000000000006', 2018, 'Placeholder: Kaizo Sky', '2018-05-06 16:29:00+00', 3377, 210, 22, 'SMW'),
+  ('0000000000000007', 2019, 'Placeholder: Bowser''s Last Stand', '2019-10-19 09:44:00+00', 198, 26, 3, 'SMB3'),
+  ('0000000000000008', 2020, 'Placeholder: One Screen Puzzle', '2020-12-31 23:59:00+00', 731, 44, 5, 'NSMBU')
+ON CONFLICT DO NOTHING;
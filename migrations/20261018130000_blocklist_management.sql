@@ -0,0 +1,2 @@
+ALTER TABLE level_blocklist
+  ADD "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
@@ -0,0 +1,11 @@
+CREATE TABLE level_reports (
+  "id" BIGSERIAL PRIMARY KEY NOT NULL,
+  "game" TEXT NOT NULL,
+  "level_id" TEXT NOT NULL,
+  "category" TEXT NOT NULL,
+  "note" TEXT,
+  "status" TEXT NOT NULL DEFAULT 'open',
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  "resolved_at" TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX level_reports_open_idx ON level_reports ("game", "level_id") WHERE "status" = 'open';
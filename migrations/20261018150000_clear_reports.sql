@@ -0,0 +1,17 @@
+CREATE TABLE clear_reports (
+  "id" BIGSERIAL PRIMARY KEY NOT NULL,
+  "game" TEXT NOT NULL,
+  "level_id" TEXT NOT NULL,
+  "source" TEXT,
+  "channel" TEXT NOT NULL,
+  "user_agent" TEXT,
+  "referer" TEXT,
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+
+  "delivery_status" TEXT NOT NULL DEFAULT 'pending',
+  "delivery_attempts" INTEGER NOT NULL DEFAULT 0,
+  "delivered_at" TIMESTAMP WITH TIME ZONE,
+  "last_delivery_error" TEXT
+);
+CREATE INDEX clear_reports_game_level_id_idx ON clear_reports ("game", "level_id", "created_at");
+CREATE INDEX clear_reports_delivery_status_idx ON clear_reports ("delivery_status");
@@ -0,0 +1,3 @@
+ALTER TABLE clear_reports
+  ADD "next_delivery_attempt_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
+CREATE INDEX clear_reports_outbox_idx ON clear_reports ("next_delivery_attempt_at") WHERE "delivery_status" IN ('pending', 'failed');
@@ -0,0 +1,3 @@
+ALTER TABLE clear_reports
+  ADD "duplicate_of" BIGINT REFERENCES clear_reports ("id") ON DELETE SET NULL,
+  ADD "confirmations" INTEGER NOT NULL DEFAULT 0;
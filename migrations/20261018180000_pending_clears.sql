@@ -0,0 +1,4 @@
+ALTER TABLE levels_smm1
+  ADD "pending_clear_until" TIMESTAMP WITH TIME ZONE;
+ALTER TABLE levels_smm2
+  ADD "pending_clear_until" TIMESTAMP WITH TIME ZONE;
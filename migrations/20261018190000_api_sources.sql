@@ -0,0 +1,11 @@
+ALTER TABLE discord_webhook_sources RENAME TO api_sources;
+ALTER TABLE api_sources
+  ADD "display_name" TEXT,
+  ADD "token_hash" TEXT UNIQUE,
+  ADD "enabled" BOOLEAN NOT NULL DEFAULT true,
+  ADD "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+  ADD "last_used_at" TIMESTAMP WITH TIME ZONE,
+  ADD "report_count" BIGINT NOT NULL DEFAULT 0;
+UPDATE api_sources SET "display_name" = "key";
+ALTER TABLE api_sources
+  ALTER "display_name" SET NOT NULL;
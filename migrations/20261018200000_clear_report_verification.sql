@@ -0,0 +1,4 @@
+ALTER TABLE clear_reports
+  ADD "verification_status" TEXT,
+  ADD "upstream_clears" BIGINT,
+  ADD "verified_at" TIMESTAMP WITH TIME ZONE;
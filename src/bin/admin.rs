@@ -0,0 +1,245 @@
+use clap::Parser;
+use smm_zerop::{
+    components::{api_sources, blocklist, discord_interactions, level_removal, settings::Settings},
+    entities::{api_source::ApiSource, blocklist_entry::BlocklistEntry, level::Game},
+    get_db_pool, init_tracing,
+};
+use time::format_description::well_known::Rfc3339;
+use tracing::info;
+
+#[derive(Debug, clap::Parser)]
+#[clap(about, version)]
+struct AdminArgs {
+    #[clap(flatten)]
+    settings: Settings,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Manages the level blocklist
+    #[clap(subcommand)]
+    Blocklist(BlocklistCommand),
+
+    /// Manages the API sources that can report clears
+    #[clap(subcommand)]
+    Source(SourceCommand),
+
+    /// Manages the levels in the randomizer
+    #[clap(subcommand)]
+    Level(LevelCommand),
+
+    /// Prints the Discord slash command definitions as JSON, ready to be
+    /// `PUT` to Discord's application commands endpoint
+    DiscordCommands,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum BlocklistCommand {
+    /// Adds a level to the blocklist, and removes it from the randomizer
+    Add {
+        /// The level ID, with or without dashes
+        level_id: String,
+
+        #[clap(long, value_enum, default_value_t = Game::Smm2)]
+        game: Game,
+
+        /// Why the level is blocklisted
+        #[clap(long)]
+        reason: Option<String>,
+    },
+
+    /// Removes a level from the blocklist. It will show up again after the
+    /// next import, if it's still uncleared.
+    Remove {
+        /// The level ID, with or without dashes
+        level_id: String,
+
+        #[clap(long, value_enum, default_value_t = Game::Smm2)]
+        game: Game,
+    },
+
+    /// Lists all blocklist entries, newest first
+    List {
+        /// Only list entries for this game
+        #[clap(long, value_enum)]
+        game: Option<Game>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum LevelCommand {
+    /// Removes a level that is known to be cleared from the randomizer, and
+    /// archives it. The importer won't bring it back for a while, see
+    /// `IMPORT_ARCHIVE_HOLD_SECS`.
+    Remove {
+        /// The level ID, with or without dashes
+        level_id: String,
+
+        #[clap(long, value_enum, default_value_t = Game::Smm2)]
+        game: Game,
+    },
+
+    /// Puts a level that was removed by hand, or verified as cleared, back
+    /// into the randomizer
+    Restore {
+        /// The level ID, with or without dashes
+        level_id: String,
+
+        #[clap(long, value_enum, default_value_t = Game::Smm2)]
+        game: Game,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum SourceCommand {
+    /// Adds a new API source, and prints its token
+    Add {
+        /// The key the source is identified by in API requests
+        key: String,
+
+        /// A human-readable name for the source
+        #[clap(long)]
+        display_name: String,
+
+        /// The URL of the tool, linked in clear notifications
+        #[clap(long)]
+        url: String,
+    },
+
+    /// Issues a new token for a source, and prints it. The old token stops
+    /// working right away.
+    RotateToken { key: String },
+
+    /// Allows the source to report clears again
+    Enable { key: String },
+
+    /// Stops accepting clear reports from the source
+    Disable { key: String },
+
+    /// Lists all sources, with their usage
+    List,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = AdminArgs::parse();
+    let settings = &args.settings;
+
+    let mut rt = tokio::runtime::Builder::new_multi_thread();
+    if let Some(threads) = settings.threads {
+        rt.worker_threads(threads);
+    }
+
+    rt.enable_all().build()?.block_on(async { run(args).await })
+}
+
+async fn run(args: AdminArgs) -> anyhow::Result<()> {
+    init_tracing(&args.settings);
+    let db = get_db_pool(args.settings.database_url).await?;
+
+    match args.command {
+        Command::Blocklist(BlocklistCommand::Add {
+            level_id,
+            game,
+            reason,
+        }) => {
+            let entry = blocklist::add(&db, game, &level_id, reason.as_deref()).await?;
+            info!(
+                "added {} level `{}` to the blocklist",
+                entry.game, entry.level_id
+            );
+        }
+        Command::Blocklist(BlocklistCommand::Remove { level_id, game }) => {
+            if blocklist::remove(&db, game, &level_id).await? {
+                info!("removed {game} level `{level_id}` from the blocklist");
+            } else {
+                anyhow::bail!("{game} level `{level_id}` is not on the blocklist");
+            }
+        }
+        Command::Blocklist(BlocklistCommand::List { game }) => {
+            for entry in BlocklistEntry::get_all(&db, game).await? {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    entry.game,
+                    entry.level_id,
+                    entry.created_at.format(&Rfc3339)?,
+                    entry.reason.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        Command::Level(LevelCommand::Remove { level_id, game }) => {
+            if level_removal::remove(&db, game, &level_id).await? {
+                info!("removed {game} level `{level_id}` from the randomizer");
+            } else {
+                anyhow::bail!("{game} level `{level_id}` is not in the randomizer");
+            }
+        }
+        Command::Level(LevelCommand::Restore { level_id, game }) => {
+            if level_removal::restore(&db, game, &level_id).await? {
+                info!("restored {game} level `{level_id}` to the randomizer");
+            } else {
+                anyhow::bail!(
+                    "{game} level `{level_id}` wasn't removed by hand or verified as cleared, or is in the randomizer already"
+                );
+            }
+        }
+        Command::Source(SourceCommand::Add {
+            key,
+            display_name,
+            url,
+        }) => {
+            let (source, token) = api_sources::create(&db, &key, &display_name, &url).await?;
+            info!("added api source `{}`", source.key);
+            println!("{token}");
+        }
+        Command::Source(SourceCommand::RotateToken { key }) => {
+            let Some(token) = api_sources::rotate_token(&db, &key).await? else {
+                anyhow::bail!("there's no api source `{key}`");
+            };
+            info!("issued a new token for api source `{key}`");
+            println!("{token}");
+        }
+        Command::Source(SourceCommand::Enable { key }) => {
+            if !ApiSource::set_enabled(&db, &key, true).await? {
+                anyhow::bail!("there's no api source `{key}`");
+            }
+            info!("enabled api source `{key}`");
+        }
+        Command::Source(SourceCommand::Disable { key }) => {
+            if !ApiSource::set_enabled(&db, &key, false).await? {
+                anyhow::bail!("there's no api source `{key}`");
+            }
+            info!("disabled api source `{key}`");
+        }
+        Command::Source(SourceCommand::List) => {
+            for source in ApiSource::get_all(&db).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    source.key,
+                    if source.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    },
+                    source.report_count,
+                    match source.last_used_at {
+                        Some(last_used_at) => last_used_at.format(&Rfc3339)?,
+                        None => "-".to_owned(),
+                    },
+                    source.display_name,
+                    source.url
+                );
+            }
+        }
+        Command::DiscordCommands => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&discord_interactions::command_definitions())?
+            );
+        }
+    }
+
+    Ok(())
+}
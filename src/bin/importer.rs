@@ -1,43 +1,166 @@
-use anyhow::bail;
+use std::path::PathBuf;
+
+use anyhow::Context;
 use clap::Parser;
 use smm_zerop::{
-    components::{settings::Settings, smm2_importer},
+    components::{
+        import_source::{
+            ImportSource, ImportSourceKind, csv_file::CsvSource, jsonl_file::JsonlSource,
+            mssql::MssqlSource,
+        },
+        importer::{self, ImportGuards, ImportOptions, ImportReport, ImportableLevel},
+        settings::Settings,
+    },
+    entities::{level::Game, smm1_level::Smm1Level, smm2_level::Smm2Level},
     get_db_pool, init_tracing,
 };
-use tokio::net::TcpStream;
-use tokio_util::compat::TokioAsyncWriteCompatExt;
+use time::Duration;
 use tracing::info;
 
+#[derive(Debug, clap::Parser)]
+#[clap(about, version)]
+struct ImporterArgs {
+    #[clap(flatten)]
+    settings: Settings,
+
+    /// Which game the levels are for. SMM1 levels can only be imported from
+    /// files.
+    #[clap(long, value_enum, default_value_t = Game::Smm2)]
+    game: Game,
+
+    /// Runs the whole import, but rolls it back at the end and prints a
+    /// report of what would have changed
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Imports even if the import guards detect a suspicious drop in levels
+    #[clap(long)]
+    force: bool,
+
+    /// Writes the import report as JSON to this path instead of printing a
+    /// human-readable one. Use `-` for stdout.
+    #[clap(long)]
+    report_json: Option<PathBuf>,
+}
+
 fn main() -> anyhow::Result<()> {
-    let settings = Settings::parse();
-    if settings.upstream_db_connstring.is_none() {
-        bail!("upstream_db_connstring must be configured for the importer to work!");
-    }
+    let args = ImporterArgs::parse();
+    let settings = &args.settings;
 
     let mut rt = tokio::runtime::Builder::new_multi_thread();
     if let Some(threads) = settings.threads {
         rt.worker_threads(threads);
     }
 
-    rt.enable_all()
-        .build()?
-        .block_on(async { run(settings).await })
+    rt.enable_all().build()?.block_on(async { run(args).await })
 }
 
-async fn run(settings: Settings) -> anyhow::Result<()> {
+async fn run(args: ImporterArgs) -> anyhow::Result<()> {
+    let settings = args.settings;
     init_tracing(&settings);
-
-    info!("connecting to upstream database...");
-    let upstream_db_config =
-        tiberius::Config::from_ado_string(&settings.upstream_db_connstring.unwrap())?;
-    let upstream_db_tcp = TcpStream::connect(upstream_db_config.get_addr()).await?;
-    upstream_db_tcp.set_nodelay(true)?;
-    let mut upstream_db_client =
-        tiberius::Client::connect(upstream_db_config, upstream_db_tcp.compat_write()).await?;
+    let options = ImportOptions {
+        dry_run: args.dry_run,
+        force: args.force,
+        guards: ImportGuards::from(&settings),
+        batch_size: settings.import_batch_size,
+        archive_hold: Duration::seconds(settings.import_archive_hold_secs.into()),
+    };
 
     info!("connecting to own database...");
-    let own_db = get_db_pool(settings.database_url).await?;
+    let own_db = get_db_pool(settings.database_url.clone()).await?;
+
+    match args.game {
+        Game::Smm1 => import::<Smm1Level>(&settings, own_db, &options, args.report_json).await,
+        Game::Smm2 => import::<Smm2Level>(&settings, own_db, &options, args.report_json).await,
+    }
+}
+
+/// Opens the configured [ImportSource], and imports levels of type `L` from
+/// it.
+async fn import<L>(
+    settings: &Settings,
+    own_db: sqlx::PgPool,
+    options: &ImportOptions,
+    report_json: Option<PathBuf>,
+) -> anyhow::Result<()>
+where
+    L: ImportableLevel,
+    MssqlSource: ImportSource<L::Raw>,
+    JsonlSource: ImportSource<L::Raw>,
+    CsvSource: ImportSource<L::Raw>,
+{
+    match settings.import_source {
+        ImportSourceKind::Mssql => {
+            info!("connecting to upstream database...");
+            let connstring = settings.upstream_db_connstring.as_deref().context(
+                "upstream_db_connstring must be configured for the mssql import source to work!",
+            )?;
+            let mut source = MssqlSource::connect(connstring).await?;
+            import_from::<L, _>(&mut source, own_db, options, report_json).await
+        }
+        ImportSourceKind::Jsonl => {
+            let path = settings
+                .import_file
+                .clone()
+                .context("import_file must be configured for the jsonl import source to work!")?;
+            import_from::<L, _>(&mut JsonlSource::new(path), own_db, options, report_json).await
+        }
+        ImportSourceKind::Csv => {
+            let path = settings
+                .import_file
+                .clone()
+                .context("import_file must be configured for the csv import source to work!")?;
+            import_from::<L, _>(&mut CsvSource::new(path), own_db, options, report_json).await
+        }
+    }
+}
+
+async fn import_from<L: ImportableLevel, S: ImportSource<L::Raw>>(
+    source: &mut S,
+    own_db: sqlx::PgPool,
+    options: &ImportOptions,
+    report_json: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let source_name = source.name();
+    let report = importer::run::<L>(source_name, source.fetch_levels(), own_db, options).await?;
+
+    finish_import(&report, options, report_json)
+}
+
+/// Logs the outcome of an import, and writes or prints the report if asked to.
+fn finish_import(
+    report: &ImportReport,
+    options: &ImportOptions,
+    report_json: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let summary = &report.summary;
+    info!(
+        "import finished ({:?}): {} added, {} updated, {} removed, {} unchanged, {} skipped by blocklist, {} quarantined",
+        report.outcome,
+        summary.added,
+        summary.updated,
+        summary.removed,
+        summary.unchanged,
+        summary.skipped_blocklisted,
+        summary.quarantined
+    );
+
+    match report_json {
+        Some(path) => write_json_report(report, &path)?,
+        None if options.dry_run => print!("{report}"),
+        None => {}
+    }
+
+    Ok(())
+}
+
+fn write_json_report(report: &ImportReport, path: &PathBuf) -> anyhow::Result<()> {
+    if path.as_os_str() == "-" {
+        println!("{}", serde_json::to_string_pretty(report)?);
+    } else {
+        std::fs::write(path, serde_json::to_vec_pretty(report)?)
+            .with_context(|| format!("could not write report to `{}`", path.display()))?;
+    }
 
-    smm2_importer::run(&mut upstream_db_client, own_db).await?;
     Ok(())
 }
@@ -1,12 +1,25 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use clap::Parser;
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, sync::Notify};
 use tracing::info;
 
 use smm_zerop::{
-    components::{app_state::AppState, lazyjinja::LazyJinja, settings::Settings},
+    components::{
+        app_state::AppState,
+        clear_reports,
+        invalidation::{self, InvalidationRegistry},
+        lazyjinja::LazyJinja,
+        level_index::{self, LevelIndex},
+        level_verification::LevelVerifier,
+        notifier::{
+            NotifierKind, discord::DiscordNotifier, json_webhook::JsonWebhookNotifier,
+            log::LogNotifier,
+        },
+        rate_limiter::RateLimiter,
+        settings::Settings,
+    },
     get_db_pool, init_tracing,
     routers::build_main_router,
 };
@@ -35,6 +48,40 @@ async fn shutdown_signal() {
     info!("shutdown signal received")
 }
 
+/// Starts the clear report delivery worker with whatever notifier is
+/// configured.
+fn spawn_delivery_worker(app_state: AppState) -> anyhow::Result<()> {
+    let settings = app_state.settings.clone();
+    let notifier_kind = settings.notifier_kind();
+    info!("delivering clear reports with the `{notifier_kind:?}` notifier");
+
+    match notifier_kind {
+        NotifierKind::Discord => {
+            let (Some(webhook_id), Some(webhook_token)) = (
+                &settings.discord_webhook_id,
+                &settings.discord_webhook_token,
+            ) else {
+                bail!("the discord notifier needs DISCORD_WEBHOOK_ID and DISCORD_WEBHOOK_TOKEN");
+            };
+            let notifier =
+                DiscordNotifier::new(&settings.discord_api_base_url, webhook_id, webhook_token);
+            tokio::spawn(clear_reports::run_delivery_worker(app_state, notifier));
+        }
+        NotifierKind::JsonWebhook => {
+            let Some(url) = &settings.notifier_webhook_url else {
+                bail!("the json-webhook notifier needs NOTIFIER_WEBHOOK_URL");
+            };
+            let notifier = JsonWebhookNotifier::new(url.clone());
+            tokio::spawn(clear_reports::run_delivery_worker(app_state, notifier));
+        }
+        NotifierKind::Log => {
+            tokio::spawn(clear_reports::run_delivery_worker(app_state, LogNotifier));
+        }
+    }
+
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     let settings = Settings::parse();
 
@@ -55,21 +102,42 @@ async fn run(settings: Settings) -> anyhow::Result<()> {
     let database = get_db_pool(settings_clone.database_url).await?;
     sqlx::migrate!().run(&database).await?;
 
-    let router = build_main_router(AppState {
+    let rate_limiter = Arc::new(RateLimiter::from_settings(&settings));
+    let level_verifier = Arc::new(LevelVerifier::from_settings(&settings));
+    let app_state = AppState {
         database,
         settings: Arc::new(settings),
         template: Arc::new(LazyJinja::new()),
-    });
+        clear_report_wakeup: Arc::new(Notify::new()),
+        rate_limiter,
+        level_verifier,
+        http_client: reqwest::Client::new(),
+        level_index: Arc::new(LevelIndex::new()),
+        invalidation: Arc::new(InvalidationRegistry::new()),
+    };
+    spawn_delivery_worker(app_state.clone())?;
+    if app_state.settings.level_index {
+        app_state
+            .invalidation
+            .register(app_state.level_index.clone());
+        tokio::spawn(level_index::run_refresher(app_state.clone()));
+    }
+    tokio::spawn(invalidation::run_listener(app_state.clone()));
+
+    let router = build_main_router(app_state);
 
     let listener = TcpListener::bind(settings_clone.listen)
         .await
         .context(format!("could not listen to `{}`", settings_clone.listen))?;
 
     info!("starting server on `{}`", settings_clone.listen);
-    axum::serve(listener, router.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .context("failed to start server")?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .context("failed to start server")?;
 
     Ok(())
 }
@@ -1,8 +1,44 @@
+/// Takes a required field out of a raw upstream level, or bails out of the
+/// conversion with a [importer::LevelConversionError]. Shared
+/// between the importers of all games.
+macro_rules! not_null {
+    ($value:expr, $field:ident) => {
+        $value.$field.clone().ok_or(
+            $crate::components::importer::LevelConversionError::MissingField(stringify!($field)),
+        )?
+    };
+}
+
+/// Turns an upstream upload datetime into the one we store. Cryptan stored
+/// them without offset. It's kinda irrelevant for our use-case, so I'll just
+/// assume they're always UTC. PostgreSQL only stores microseconds, so
+/// anything more precise would show up as a change in every single import
+/// diff.
+fn upstream_upload_datetime(naive_datetime: time::PrimitiveDateTime) -> time::OffsetDateTime {
+    naive_datetime
+        .replace_microsecond(naive_datetime.microsecond())
+        .expect("microsecond to be in range")
+        .assume_offset(time::macros::offset!(UTC))
+}
+
+pub mod api_sources;
 pub mod app_state;
+pub mod blocklist;
+pub mod clear_reports;
 pub mod deserializers;
-pub mod discord_webhook;
+pub mod discord_interactions;
+pub mod import_source;
+pub mod importer;
+pub mod invalidation;
 pub mod lazyjinja;
+pub mod level_index;
+pub mod level_removal;
+pub mod level_reports;
+pub mod level_verification;
+pub mod notifier;
+pub mod rate_limiter;
 pub mod settings;
+pub mod smm1_importer;
 pub mod smm2_importer;
 pub mod tpl_helpers;
 pub mod vite_assets;
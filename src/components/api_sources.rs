@@ -0,0 +1,171 @@
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::entities::api_source::ApiSource;
+
+#[derive(Debug, Error)]
+pub enum ApiSourceError {
+    #[error("invalid api token")]
+    InvalidToken,
+
+    #[error("reporting on behalf of a source requires that source's api token")]
+    TokenRequired,
+
+    #[error("api source `{0}` is disabled")]
+    Disabled(String),
+
+    #[error("this api token belongs to a different source than `{0}`")]
+    SourceMismatch(String),
+
+    #[error(transparent)]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Generates a new random API token. Only its hash ever gets stored, so this
+/// is the one chance to show it to whoever set up the source.
+fn generate_token() -> String {
+    format!(
+        "smmz_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// Tokens are long random strings, so a plain SHA-256 is enough here. There's
+/// nothing to brute-force.
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Creates a new API source, and returns it together with its token.
+pub async fn create(
+    db: &PgPool,
+    key: &str,
+    display_name: &str,
+    url: &str,
+) -> Result<(ApiSource, String), ApiSourceError> {
+    let token = generate_token();
+    let source = ApiSource::store(db, key, display_name, url, &hash_token(&token)).await?;
+    Ok((source, token))
+}
+
+/// Issues a new token for an existing source, which invalidates the old one.
+/// Returns `None` if there's no such source.
+pub async fn rotate_token(db: &PgPool, key: &str) -> Result<Option<String>, ApiSourceError> {
+    let token = generate_token();
+    Ok(ApiSource::set_token_hash(db, key, &hash_token(&token))
+        .await?
+        .then_some(token))
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// The hash of the request's bearer token, if there is one. The rate limiter
+/// uses this to recognize tokens it has seen before.
+pub fn bearer_token_hash(headers: &HeaderMap) -> Option<String> {
+    bearer_token(headers).map(hash_token)
+}
+
+/// The [ApiSource] a request's bearer token belongs to, enabled or not. `None`
+/// if there is no token, or if it doesn't belong to any source. If the rate
+/// limiter had to look this up, it keeps it in the request's extensions, so
+/// [authenticate] doesn't have to hash and query again.
+#[derive(Clone, Debug)]
+pub struct TokenOwner(pub Option<ApiSource>);
+
+/// Looks up the [TokenOwner] for the request's bearer token, without
+/// complaining about anything.
+pub async fn identify(db: &PgPool, headers: &HeaderMap) -> Result<TokenOwner, sqlx::Error> {
+    let Some(token) = bearer_token(headers) else {
+        return Ok(TokenOwner(None));
+    };
+
+    Ok(TokenOwner(
+        ApiSource::get_by_token_hash(db, &hash_token(token)).await?,
+    ))
+}
+
+/// Figures out which [ApiSource] an API request comes from, based on the
+/// bearer token in the `Authorization` header. Requests without a token are
+/// anonymous, which is fine unless they claim to be from a source, or
+/// `require_token` is set. If the token was already looked up, pass the
+/// `token_owner` from the request's extensions.
+///
+/// Sources that were set up before sources had tokens don't have one until
+/// `admin source rotate-token` issues it. Until then, they can still claim
+/// their source without a token, unless `require_token` is set, so upgrading
+/// doesn't break them.
+pub async fn authenticate(
+    db: &PgPool,
+    headers: &HeaderMap,
+    token_owner: Option<TokenOwner>,
+    claimed_source: Option<&str>,
+    require_token: bool,
+) -> Result<Option<ApiSource>, ApiSourceError> {
+    if bearer_token(headers).is_none() {
+        return match (claimed_source, require_token) {
+            (None, false) => Ok(None),
+            (Some(claimed_source), false) => authenticate_without_token(db, claimed_source).await,
+            _ => Err(ApiSourceError::TokenRequired),
+        };
+    }
+
+    let token_owner = match token_owner {
+        Some(token_owner) => token_owner,
+        None => identify(db, headers).await?,
+    };
+    let source = token_owner.0.ok_or(ApiSourceError::InvalidToken)?;
+
+    if !source.enabled {
+        return Err(ApiSourceError::Disabled(source.key));
+    }
+    if let Some(claimed_source) = claimed_source
+        && claimed_source != source.key
+    {
+        return Err(ApiSourceError::SourceMismatch(claimed_source.to_owned()));
+    }
+
+    Ok(Some(source))
+}
+
+async fn authenticate_without_token(
+    db: &PgPool,
+    claimed_source: &str,
+) -> Result<Option<ApiSource>, ApiSourceError> {
+    let source = ApiSource::get_without_token(db, claimed_source)
+        .await?
+        .ok_or(ApiSourceError::TokenRequired)?;
+    if !source.enabled {
+        return Err(ApiSourceError::Disabled(source.key));
+    }
+
+    warn!(
+        "api source `{}` reported without a token, issue one with `admin source rotate-token {}`",
+        source.key, source.key
+    );
+    Ok(Some(source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_tokens_are_unique_and_hash_consistently() {
+        let token = generate_token();
+        assert!(token.starts_with("smmz_"));
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_eq!(hash_token(&token).len(), 64);
+    }
+}
@@ -1,9 +1,30 @@
 use std::sync::Arc;
 
+use tokio::sync::Notify;
+
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub database: sqlx::PgPool,
     pub settings: Arc<super::settings::Settings>,
     pub template: Arc<super::lazyjinja::LazyJinja>,
+
+    /// Wakes up the clear report delivery worker, see
+    /// [super::clear_reports::run_delivery_worker].
+    pub clear_report_wakeup: Arc<Notify>,
+
+    pub rate_limiter: Arc<super::rate_limiter::RateLimiter>,
+    pub level_verifier: Arc<super::level_verification::LevelVerifier>,
+
+    /// For requests that handlers send on their own, like the follow-ups of
+    /// deferred Discord interactions. Clones share one connection pool.
+    pub http_client: reqwest::Client,
+
+    /// Only loaded if enabled in the settings, see
+    /// [super::level_index::run_refresher].
+    pub level_index: Arc<super::level_index::LevelIndex>,
+
+    /// Subsystems that need to hear about changes to level data, see
+    /// [super::invalidation::run_listener].
+    pub invalidation: Arc<super::invalidation::InvalidationRegistry>,
 }
@@ -0,0 +1,101 @@
+use sqlx::{PgConnection, PgPool};
+
+use crate::{
+    components::invalidation::InvalidationEvent,
+    entities::{
+        blocklist_entry::BlocklistEntry,
+        level::{Game, Level},
+        level_archive::RemovalReason,
+        smm1_level::Smm1Level,
+        smm2_level::Smm2Level,
+    },
+};
+
+/// Everything that can go wrong when managing the blocklist.
+#[derive(Debug, thiserror::Error)]
+pub enum BlocklistError {
+    #[error("`{0}` is not a valid level ID")]
+    InvalidLevelId(String),
+
+    #[error(transparent)]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Adds a level to the blocklist. The level is removed from the randomizer
+/// right away and archived as [RemovalReason::Blocklisted], instead of waiting
+/// for the next import to filter it out.
+pub async fn add(
+    db: &PgPool,
+    game: Game,
+    raw_level_id: &str,
+    reason: Option<&str>,
+) -> Result<BlocklistEntry, BlocklistError> {
+    let mut db_transaction = db.begin().await?;
+    let entry = add_in_transaction(&mut db_transaction, game, raw_level_id, reason).await?;
+    db_transaction.commit().await?;
+
+    Ok(entry)
+}
+
+/// Like [add], but as part of a transaction the caller is responsible for
+/// committing.
+pub async fn add_in_transaction(
+    conn: &mut PgConnection,
+    game: Game,
+    raw_level_id: &str,
+    reason: Option<&str>,
+) -> Result<BlocklistEntry, BlocklistError> {
+    match game {
+        Game::Smm1 => add_level::<Smm1Level>(conn, raw_level_id, reason).await,
+        Game::Smm2 => add_level::<Smm2Level>(conn, raw_level_id, reason).await,
+    }
+}
+
+async fn add_level<L: Level>(
+    conn: &mut PgConnection,
+    raw_level_id: &str,
+    reason: Option<&str>,
+) -> Result<BlocklistEntry, BlocklistError> {
+    let level_id = normalized_level_id::<L>(raw_level_id)?;
+
+    let entry = BlocklistEntry::store(&mut *conn, L::GAME, &level_id, reason).await?;
+    InvalidationEvent::BlocklistChanged {
+        game: L::GAME,
+        level_id: level_id.clone(),
+    }
+    .publish(&mut *conn)
+    .await?;
+    L::archive_many(conn, &[level_id], RemovalReason::Blocklisted).await?;
+
+    Ok(entry)
+}
+
+/// Removes a level from the blocklist. Returns `false` if the level wasn't on
+/// it. The level does not come back right away: if it's still uncleared, the
+/// next import will pick it up again.
+pub async fn remove(db: &PgPool, game: Game, raw_level_id: &str) -> Result<bool, BlocklistError> {
+    let level_id = match game {
+        Game::Smm1 => normalized_level_id::<Smm1Level>(raw_level_id)?,
+        Game::Smm2 => normalized_level_id::<Smm2Level>(raw_level_id)?,
+    };
+
+    let mut db_transaction = db.begin().await?;
+    let deleted = BlocklistEntry::delete(&mut *db_transaction, game, &level_id).await?;
+    if deleted {
+        InvalidationEvent::BlocklistChanged { game, level_id }
+            .publish(&mut *db_transaction)
+            .await?;
+    }
+    db_transaction.commit().await?;
+
+    Ok(deleted)
+}
+
+fn normalized_level_id<L: Level>(raw_level_id: &str) -> Result<String, BlocklistError> {
+    let level_id = L::normalized_internal_level_id(raw_level_id);
+    if !L::is_valid_level_id(&level_id) {
+        return Err(BlocklistError::InvalidLevelId(raw_level_id.to_owned()));
+    }
+
+    Ok(level_id)
+}
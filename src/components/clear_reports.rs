@@ -0,0 +1,275 @@
+use axum::http::{HeaderMap, header};
+use time::{Duration, OffsetDateTime};
+use tracing::{error, info, warn};
+
+use crate::{
+    components::{
+        app_state::AppState,
+        invalidation::InvalidationEvent,
+        notifier::{ClearNotification, Notifier, NotifierError},
+    },
+    entities::{
+        api_source::ApiSource,
+        clear_report::{
+            ClearReport, ClientHints, DeliveryStatus, NewClearReport, ReportChannel,
+            VerificationStatus,
+        },
+        level::Level,
+        level_archive::RemovalReason,
+    },
+};
+
+/// Clamps header values so a silly client can't fill the table with junk.
+const MAX_HINT_LENGTH: usize = 512;
+
+/// How many reports the delivery worker claims at once.
+const DELIVERY_BATCH_SIZE: i64 = 20;
+
+/// How long claimed reports are hidden from other workers. Needs to be longer
+/// than a whole batch usually takes.
+const DELIVERY_LEASE: Duration = Duration::minutes(2);
+
+/// How often the worker checks for due reports if nobody wakes it up. This is
+/// what picks up retries.
+const DELIVERY_POLL_INTERVAL: Duration = Duration::seconds(5);
+
+const RETRY_BASE_DELAY: Duration = Duration::seconds(10);
+const RETRY_MAX_DELAY: Duration = Duration::hours(1);
+
+pub fn client_hints(headers: &HeaderMap) -> ClientHints {
+    let header_value = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.chars().take(MAX_HINT_LENGTH).collect())
+    };
+
+    ClientHints {
+        user_agent: header_value(header::USER_AGENT),
+        referer: header_value(header::REFERER),
+    }
+}
+
+/// Stores a clear report and pokes the delivery worker. Once this returns,
+/// the report is safe in the database and will be delivered eventually,
+/// so there's no need to make the user wait for that.
+///
+/// If the same level was already reported within the dedup window, the new
+/// report is recorded as a confirmation of that one instead, and not
+/// delivered again. Either way, the level is hidden from the randomizer for
+/// the grace period.
+///
+/// If clear verification is enabled, the report is checked first. Confirmed
+/// clears archive the level right away, and reports for levels without any
+/// clears are withheld instead of delivered.
+#[tracing::instrument(skip(app_state, client_hints), fields(game = %L::GAME))]
+pub async fn submit<L: Level>(
+    app_state: &AppState,
+    level_id: &str,
+    source: Option<&str>,
+    channel: ReportChannel,
+    client_hints: &ClientHints,
+) -> Result<ClearReport, sqlx::Error> {
+    let game = L::GAME;
+    let dedup_window = Duration::seconds(app_state.settings.clear_report_dedup_window_secs.into());
+    let grace_period = Duration::seconds(app_state.settings.clear_report_grace_period_secs.into());
+
+    // Done before the transaction, so a slow API doesn't hold the lock.
+    let verification = app_state.level_verifier.verify(game, level_id).await;
+    let verification_status = verification.map(|v| v.status);
+
+    let mut db_transaction = app_state.database.begin().await?;
+    // Contradicted reports are withheld, so they shouldn't confirm anything.
+    let original = if dedup_window.is_positive()
+        && verification_status != Some(VerificationStatus::Contradicted)
+    {
+        ClearReport::lock_level(&mut db_transaction, game, level_id).await?;
+        ClearReport::find_recent_original(&mut *db_transaction, game, level_id, dedup_window)
+            .await?
+    } else {
+        None
+    };
+
+    let report = ClearReport::store(
+        &mut *db_transaction,
+        &NewClearReport {
+            game,
+            level_id,
+            source,
+            channel,
+            client_hints,
+            duplicate_of: original.as_ref().map(|o| o.id),
+            verification,
+        },
+    )
+    .await?;
+    if let Some(original) = &original {
+        original.add_confirmation(&mut *db_transaction).await?;
+    }
+    match verification_status {
+        Some(VerificationStatus::Verified) => {
+            L::archive_many(
+                &mut db_transaction,
+                &[level_id.to_owned()],
+                RemovalReason::VerifiedCleared,
+            )
+            .await?;
+            InvalidationEvent::LevelArchived {
+                game,
+                level_id: level_id.to_owned(),
+            }
+            .publish(&mut *db_transaction)
+            .await?;
+        }
+        Some(VerificationStatus::Contradicted) => {}
+        Some(VerificationStatus::Unverified) | None if grace_period.is_positive() => {
+            let until = report.created_at + grace_period;
+            L::hide_pending_clear(&mut db_transaction, level_id, until).await?;
+            InvalidationEvent::LevelHidden {
+                game,
+                level_id: level_id.to_owned(),
+                until,
+            }
+            .publish(&mut *db_transaction)
+            .await?;
+        }
+        Some(VerificationStatus::Unverified) | None => {}
+    }
+    db_transaction.commit().await?;
+
+    match (report.delivery_status, original) {
+        (DeliveryStatus::Withheld, _) => warn!(
+            "clear report {} withheld, the level has no clears upstream",
+            report.id
+        ),
+        (_, Some(original)) => info!(
+            "clear report {} coalesced into report {}",
+            report.id, original.id
+        ),
+        (_, None) => app_state.clear_report_wakeup.notify_one(),
+    }
+
+    Ok(report)
+}
+
+/// Exponential backoff for failed deliveries: 10s, 20s, 40s, ... capped at an
+/// hour.
+fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    (RETRY_BASE_DELAY * 2_i32.pow(exponent)).min(RETRY_MAX_DELAY)
+}
+
+/// What the worker should do after a batch.
+enum NextBatch {
+    /// The batch was full, so there's probably more.
+    Immediately,
+    /// The notifier rate-limited us.
+    After(Duration),
+    /// Nothing left to do for now.
+    WhenWokenUp,
+}
+
+/// Looks up the URL of the [ApiSource] a report came from.
+async fn source_url(app_state: &AppState, source: Option<&str>) -> Option<String> {
+    match ApiSource::get(&app_state.database, source?).await {
+        Ok(source) => source.map(|s| s.url),
+        Err(err) => {
+            error!("fetching api source failed: {:?}", err);
+            None
+        }
+    }
+}
+
+async fn deliver_due_reports<N: Notifier>(
+    app_state: &AppState,
+    notifier: &N,
+) -> Result<NextBatch, sqlx::Error> {
+    let reports =
+        ClearReport::claim_due(&app_state.database, DELIVERY_BATCH_SIZE, DELIVERY_LEASE).await?;
+    let batch_was_full = reports.len() as i64 == DELIVERY_BATCH_SIZE;
+
+    let mut reports = reports.into_iter();
+    while let Some(report) = reports.next() {
+        let notification = ClearNotification::new(
+            &report,
+            source_url(app_state, report.source.as_deref()).await,
+        );
+
+        match notifier.notify_clear(&notification).await {
+            Ok(()) => report.mark_delivered(&app_state.database).await?,
+            Err(NotifierError::RateLimited(retry_after)) => {
+                warn!("rate limited by the notifier, pausing deliveries for {retry_after}");
+                let retry_at = OffsetDateTime::now_utc() + retry_after;
+                for report in std::iter::once(report).chain(reports) {
+                    report.postpone(&app_state.database, retry_at).await?;
+                }
+                return Ok(NextBatch::After(retry_after));
+            }
+            Err(err) => {
+                let attempts = report.delivery_attempts + 1;
+                let retry_at = (attempts < app_state.settings.clear_report_max_attempts)
+                    .then(|| OffsetDateTime::now_utc() + retry_delay(attempts));
+                if retry_at.is_none() {
+                    error!(
+                        "dead-lettering clear report {} after {attempts} attempts: {err}",
+                        report.id
+                    );
+                } else {
+                    warn!("delivering clear report {} failed: {err}", report.id);
+                }
+                report
+                    .mark_failed(&app_state.database, &err.to_string(), retry_at)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(if batch_was_full {
+        NextBatch::Immediately
+    } else {
+        NextBatch::WhenWokenUp
+    })
+}
+
+/// Delivers queued clear reports with the given [Notifier], forever. This
+/// runs as a background task in the web server, and gets woken up by
+/// [submit]. Since reports are claimed with a lease, running more than one
+/// server is fine.
+pub async fn run_delivery_worker<N: Notifier>(app_state: AppState, notifier: N) {
+    info!("starting clear report delivery worker");
+
+    loop {
+        let poll_interval = match deliver_due_reports(&app_state, &notifier).await {
+            Ok(NextBatch::Immediately) => continue,
+            Ok(NextBatch::After(pause)) => {
+                // New reports don't change the notifier's mind, so no waking up
+                // early here.
+                tokio::time::sleep(pause.try_into().unwrap_or_default()).await;
+                continue;
+            }
+            Ok(NextBatch::WhenWokenUp) => DELIVERY_POLL_INTERVAL,
+            Err(err) => {
+                error!("delivering clear reports failed: {err:?}");
+                DELIVERY_POLL_INTERVAL
+            }
+        };
+
+        tokio::select! {
+            () = app_state.clear_report_wakeup.notified() => {},
+            () = tokio::time::sleep(poll_interval.try_into().unwrap_or_default()) => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::seconds(10));
+        assert_eq!(retry_delay(2), Duration::seconds(20));
+        assert_eq!(retry_delay(4), Duration::seconds(80));
+        assert_eq!(retry_delay(30), Duration::hours(1));
+    }
+}
@@ -29,3 +29,17 @@ where
         Some(s) => T::deserialize(s.into_deserializer()).map(Some),
     }
 }
+
+/// Like [empty_string_as_none_enum], for parameters that can be repeated.
+/// Empty values are skipped, so `?style=` still means "any style".
+pub fn empty_strings_skipped_enum<'de, D, T>(de: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Vec::<String>::deserialize(de)?
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .map(|s| T::deserialize(s.into_deserializer()))
+        .collect()
+}
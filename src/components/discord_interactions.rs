@@ -0,0 +1,526 @@
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tracing::error;
+
+use crate::{
+    components::{app_state::AppState, clear_reports, level_index::pick_random_level, tpl_helpers},
+    entities::{
+        clear_report::{ClientHints, DeliveryStatus, ReportChannel},
+        level::Level,
+        smm2_level::{FilterParams, Smm2Level},
+    },
+};
+
+/// Discord's blurple, used for all embeds.
+const EMBED_COLOR: u32 = 0x5865f2;
+
+/// Message flag that makes a response only visible to whoever ran the
+/// command. Used for errors, so they don't clutter the channel.
+const EPHEMERAL: u32 = 1 << 6;
+
+/// Parses the application's public key, as shown in the Discord developer
+/// portal.
+pub fn parse_public_key(hex_key: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim()).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// Checks the `X-Signature-Ed25519` of an interaction request. Discord signs
+/// the `X-Signature-Timestamp` followed by the raw body, and refuses to send
+/// interactions to endpoints that don't reject bad signatures.
+pub fn verify_signature(
+    public_key: &VerifyingKey,
+    timestamp: &str,
+    body: &[u8],
+    hex_signature: &str,
+) -> bool {
+    let Some(signature) = hex::decode(hex_signature.trim())
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+    else {
+        return false;
+    };
+
+    let message = [timestamp.as_bytes(), body].concat();
+    public_key.verify(&message, &signature).is_ok()
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(from = "u8")]
+pub enum InteractionType {
+    Ping,
+    ApplicationCommand,
+    Other(u8),
+}
+
+impl From<u8> for InteractionType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Ping,
+            2 => Self::ApplicationCommand,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// The parts of an incoming interaction we care about.
+#[derive(Debug, Deserialize)]
+pub struct Interaction {
+    #[serde(rename = "type")]
+    pub kind: InteractionType,
+    pub data: Option<CommandData>,
+
+    /// Together with the [Self::token], this is needed to edit the response
+    /// of a deferred interaction.
+    pub application_id: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandData {
+    pub name: String,
+    #[serde(default)]
+    pub options: Vec<CommandOption>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommandOption {
+    pub name: String,
+    pub value: Value,
+}
+
+impl CommandData {
+    /// Returns the option as a string, no matter if Discord sent a string or
+    /// a number.
+    fn option(&self, name: &str) -> Option<String> {
+        self.options
+            .iter()
+            .find(|o| o.name == name)
+            .map(|o| match &o.value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            })
+    }
+
+    /// Turns the command's options into [FilterParams], the same way they'd
+    /// be parsed from the randomizer's query string. Also returns that query
+    /// string, so the response can link to the randomizer.
+    fn filter_params(&self) -> Result<(FilterParams, String), String> {
+        let query = serde_urlencoded::to_string(
+            self.options
+                .iter()
+                .filter_map(|o| Some((o.name.as_str(), self.option(&o.name)?)))
+                .collect::<Vec<_>>(),
+        )
+        .map_err(|e| e.to_string())?;
+        let filters = serde_html_form::from_str(&query).map_err(|e| e.to_string())?;
+        Ok((filters, query))
+    }
+}
+
+/// What we send back. Only `PONG`, "reply with a message", and "reply later"
+/// are needed.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct InteractionResponse {
+    #[serde(rename = "type")]
+    pub kind: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl InteractionResponse {
+    fn pong() -> Self {
+        Self {
+            kind: 1,
+            data: None,
+        }
+    }
+
+    fn message(data: Value) -> Self {
+        Self {
+            kind: 4,
+            data: Some(data),
+        }
+    }
+
+    /// Shows a public "thinking..." message, which has to be replaced with
+    /// the actual response within 15 minutes, see [send_deferred_response].
+    fn deferred() -> Self {
+        Self {
+            kind: 5,
+            data: None,
+        }
+    }
+
+    fn embed(embed: Value) -> Self {
+        Self::message(json!({ "embeds": [embed] }))
+    }
+
+    fn error(message: &str) -> Self {
+        Self::message(json!({ "content": message, "flags": EPHEMERAL }))
+    }
+}
+
+/// Handles an already verified interaction.
+pub async fn handle(
+    app_state: &AppState,
+    interaction: &Interaction,
+    client_hints: &ClientHints,
+) -> InteractionResponse {
+    let command = match (&interaction.kind, &interaction.data) {
+        (InteractionType::Ping, _) => return InteractionResponse::pong(),
+        (InteractionType::ApplicationCommand, Some(command)) => command,
+        _ => return InteractionResponse::error("Sorry, I don't know what to do with that."),
+    };
+
+    match command.name.as_str() {
+        "random" => random_command(app_state, command).await,
+        "cleared" => cleared_command(app_state, interaction, command, client_hints).await,
+        _ => InteractionResponse::error("Sorry, I don't know that command."),
+    }
+}
+
+async fn random_command(app_state: &AppState, command: &CommandData) -> InteractionResponse {
+    let (filters, query) = match command.filter_params() {
+        Ok(filters) => filters,
+        Err(err) => return InteractionResponse::error(&format!("Invalid filters: {err}")),
+    };
+
+    let level =
+        pick_random_level::<Smm2Level>(app_state, &Smm2Level::effective_filters(&filters)).await;
+
+    match level {
+        Ok((Some(level), _)) => InteractionResponse::embed(level_embed(app_state, &level, &query)),
+        Ok((None, _)) => InteractionResponse::error("No uncleared level matches these filters."),
+        Err(err) => {
+            error!("picking a random level for discord failed: {err:?}");
+            InteractionResponse::error("Something went wrong, please try again later.")
+        }
+    }
+}
+
+fn level_embed(app_state: &AppState, level: &Smm2Level, query: &str) -> Value {
+    let mut fields = vec![
+        json!({ "name": "Level ID", "value": Smm2Level::formatted_level_id(&level.id), "inline": true }),
+        json!({ "name": "Uploaded", "value": level.year.to_string(), "inline": true }),
+        json!({ "name": "Style", "value": level.style, "inline": true }),
+        json!({ "name": "Attempts", "value": level.attempts.to_string(), "inline": true }),
+        json!({ "name": "Footprints", "value": level.footprints.to_string(), "inline": true }),
+        json!({ "name": "Clear check", "value": tpl_helpers::ms_to_minsecs(level.clearcheck_ms), "inline": true }),
+    ];
+    if let Some(clear_condition) = level.clear_condition {
+        fields.push(json!({
+            "name": "Clear condition",
+            "value": Smm2Level::clear_condition_text(clear_condition, level.clear_condition_magnitude),
+        }));
+    }
+    if !level.tags.is_empty() {
+        fields.push(json!({ "name": "Tags", "value": tpl_helpers::tag_list(level.tags.clone()) }));
+    }
+
+    let mut url = app_state
+        .settings
+        .public_url
+        .join("/smm2/random_level/")
+        .ok();
+    if let Some(url) = &mut url {
+        url.set_query(Some(query).filter(|q| !q.is_empty()));
+    }
+
+    json!({
+        "title": level.title,
+        "description": level.description.as_deref().unwrap_or_default(),
+        "url": url.map(|u| u.to_string()),
+        "color": EMBED_COLOR,
+        "fields": fields,
+        "footer": { "text": "Use /cleared with the level ID once you've cleared it!" },
+    })
+}
+
+/// Reporting a clear can take longer than the three seconds Discord waits
+/// for a response, since the report might be verified against a level-data
+/// API first. So after the quick checks, the report is submitted in the
+/// background, and the deferred response is edited once it's done.
+async fn cleared_command(
+    app_state: &AppState,
+    interaction: &Interaction,
+    command: &CommandData,
+    client_hints: &ClientHints,
+) -> InteractionResponse {
+    let Some(raw_id) = command.option("level_id") else {
+        return InteractionResponse::error("Please tell me which level you cleared.");
+    };
+
+    let level_id = Smm2Level::normalized_internal_level_id(&raw_id);
+    if !Smm2Level::is_valid_level_id(&level_id) {
+        return InteractionResponse::error("That doesn't look like a valid level ID.");
+    }
+    if !Smm2Level::id_exists(&app_state.database, &level_id).await {
+        return InteractionResponse::error("That level isn't on the uncleared list.");
+    }
+
+    let (Some(application_id), Some(token)) = (&interaction.application_id, &interaction.token)
+    else {
+        return InteractionResponse::error("Sorry, I don't know what to do with that.");
+    };
+    let webhook_url = interaction_webhook_url(
+        &app_state.settings.discord_api_base_url,
+        application_id,
+        token,
+    );
+
+    let app_state = app_state.clone();
+    let client_hints = client_hints.clone();
+    tokio::spawn(async move {
+        let response = report_clear(&app_state, &level_id, &client_hints).await;
+        if let Err(err) =
+            send_deferred_response(&app_state.http_client, &webhook_url, &response).await
+        {
+            error!("sending the discord response for a clear failed: {err:?}");
+        }
+    });
+
+    InteractionResponse::deferred()
+}
+
+async fn report_clear(
+    app_state: &AppState,
+    level_id: &str,
+    client_hints: &ClientHints,
+) -> InteractionResponse {
+    let report = clear_reports::submit::<Smm2Level>(
+        app_state,
+        level_id,
+        None,
+        ReportChannel::Discord,
+        client_hints,
+    )
+    .await;
+
+    match report {
+        Ok(report) if report.delivery_status == DeliveryStatus::Withheld => {
+            InteractionResponse::error(
+                "According to the level data, nobody has cleared that level yet. \
+                If you just cleared it, please try again in a few minutes.",
+            )
+        }
+        Ok(_) => InteractionResponse::embed(json!({
+            "title": "Clear reported!",
+            "description": format!(
+                "Thanks! **{}** has been reported as cleared.",
+                Smm2Level::formatted_level_id(level_id)
+            ),
+            "color": EMBED_COLOR,
+        })),
+        Err(err) => {
+            error!("reporting a clear from discord failed: {err:?}");
+            InteractionResponse::error("Something went wrong, please try again later.")
+        }
+    }
+}
+
+/// The webhook an interaction's follow-up messages are sent to.
+fn interaction_webhook_url(api_base_url: &Url, application_id: &str, token: &str) -> Url {
+    let mut url = api_base_url.clone();
+    url.path_segments_mut()
+        .expect("the discord api url to be a base url")
+        .pop_if_empty()
+        .extend(["webhooks", application_id, token]);
+    url
+}
+
+/// The URL of the message a deferred interaction response turned into.
+fn original_response_url(webhook_url: &Url) -> Url {
+    let mut url = webhook_url.clone();
+    url.path_segments_mut()
+        .expect("the discord api url to be a base url")
+        .extend(["messages", "@original"]);
+    url
+}
+
+/// Replaces the "thinking..." message of a deferred interaction with the
+/// actual response. That message is public, and editing it can't change
+/// that, so ephemeral responses (which are errors) are sent as an ephemeral
+/// follow-up instead, and the "thinking..." message is deleted.
+async fn send_deferred_response(
+    client: &reqwest::Client,
+    webhook_url: &Url,
+    response: &InteractionResponse,
+) -> Result<(), reqwest::Error> {
+    let data = response.data.clone().unwrap_or_default();
+    let ephemeral = data
+        .get("flags")
+        .and_then(Value::as_u64)
+        .is_some_and(|flags| flags & u64::from(EPHEMERAL) != 0);
+
+    if !ephemeral {
+        client
+            .patch(original_response_url(webhook_url))
+            .json(&data)
+            .send()
+            .await?
+            .error_for_status()?;
+        return Ok(());
+    }
+
+    client
+        .post(webhook_url.clone())
+        .json(&data)
+        .send()
+        .await?
+        .error_for_status()?;
+    client
+        .delete(original_response_url(webhook_url))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// The slash command definitions, in the format Discord's "bulk overwrite
+/// global application commands" endpoint expects.
+pub fn command_definitions() -> Value {
+    let choices = |values: &[&str]| {
+        values
+            .iter()
+            .map(|v| json!({ "name": tpl_helpers::tag_name(v), "value": v }))
+            .collect::<Vec<_>>()
+    };
+    let integer = |name: &str, description: &str| json!({ "type": 4, "name": name, "description": description, "required": false });
+    let string = |name: &str, description: &str, values: &[&str]| {
+        json!({
+            "type": 3,
+            "name": name,
+            "description": description,
+            "required": false,
+            "choices": choices(values),
+        })
+    };
+
+    json!([
+        {
+            "name": "random",
+            "description": "Picks a random uncleared Super Mario Maker 2 level",
+            "options": [
+                integer("year", "Upload year, or -1 for any year"),
+                string("style", "Game style", &["smb1", "smb3", "smw", "nsmbu", "sm3dw"]),
+                string("theme", "Course theme", &[
+                    "airship", "castle", "desert", "forest", "ghost_house", "overworld", "sky",
+                    "snow", "underground",
+                ]),
+                string("tag", "Level tag", &[
+                    "art", "auto_mario", "autoscroll", "boss_battle", "link", "multiplayer_versus",
+                    "music", "puzzle_solving", "shooter", "short_and_sweet", "single_player",
+                    "speedrun", "standard", "technical", "themed",
+                ]),
+                string("clear_condition_group", "Clear condition", &[
+                    "none", "no_jumping", "no_damage", "defeating_enemies", "powerup_finish",
+                    "holding_activating", "collecting",
+                ]),
+                integer("min_attempts", "Minimum number of attempts"),
+                integer("max_attempts", "Maximum number of attempts"),
+                integer("min_footprints", "Minimum number of footprints"),
+                integer("max_footprints", "Maximum number of footprints"),
+            ],
+        },
+        {
+            "name": "cleared",
+            "description": "Reports an uncleared Super Mario Maker 2 level as cleared",
+            "options": [
+                {
+                    "type": 3,
+                    "name": "level_id",
+                    "description": "The level ID, like ABC-DEF-GHJ",
+                    "required": true,
+                },
+            ],
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    #[test]
+    fn signatures_are_verified() {
+        let signing_key = signing_key();
+        let public_key =
+            parse_public_key(&hex::encode(signing_key.verifying_key().as_bytes())).unwrap();
+
+        let body = br#"{"type":1}"#;
+        let signature = hex::encode(
+            signing_key
+                .sign(&[b"1700000000".as_slice(), body].concat())
+                .to_bytes(),
+        );
+
+        assert!(verify_signature(
+            &public_key,
+            "1700000000",
+            body,
+            &signature
+        ));
+        assert!(!verify_signature(
+            &public_key,
+            "1700000001",
+            body,
+            &signature
+        ));
+        assert!(!verify_signature(
+            &public_key,
+            "1700000000",
+            br#"{"type":2}"#,
+            &signature
+        ));
+        assert!(!verify_signature(&public_key, "1700000000", body, "nope"));
+    }
+
+    #[test]
+    fn response_urls_are_built_from_the_base_url() {
+        let webhook_url =
+            interaction_webhook_url(&"https://discord.com/api/".parse().unwrap(), "1234", "abcd");
+        assert_eq!(
+            webhook_url.as_str(),
+            "https://discord.com/api/webhooks/1234/abcd"
+        );
+        assert_eq!(
+            original_response_url(&webhook_url).as_str(),
+            "https://discord.com/api/webhooks/1234/abcd/messages/@original"
+        );
+    }
+
+    #[test]
+    fn command_options_become_filter_params() {
+        let interaction: Interaction = serde_json::from_value(json!({
+            "type": 2,
+            "data": {
+                "name": "random",
+                "options": [
+                    { "name": "year", "type": 4, "value": 2023 },
+                    { "name": "style", "type": 3, "value": "smw" },
+                    { "name": "tag", "type": 3, "value": "speedrun" },
+                ],
+            },
+        }))
+        .unwrap();
+        assert_eq!(interaction.kind, InteractionType::ApplicationCommand);
+
+        let (filters, query) = interaction.data.unwrap().filter_params().unwrap();
+        assert_eq!(filters.year, Some(2023));
+        assert!(matches!(
+            filters.style[..],
+            [crate::entities::smm2_level::Style::SMW]
+        ));
+        assert_eq!(query, "year=2023&style=smw&tag=speedrun");
+    }
+}
@@ -0,0 +1,87 @@
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+use time::PrimitiveDateTime;
+
+use crate::entities::quarantined_level::QuarantinedLevel;
+
+pub mod csv_file;
+pub mod jsonl_file;
+pub mod mssql;
+
+time::serde::format_description!(
+    upstream_datetime,
+    PrimitiveDateTime,
+    "[year]-[month]-[day] [hour]:[minute]:[second][optional [.[subsecond]]]"
+);
+
+/// Specifies where the importer gets its levels from
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ImportSourceKind {
+    Mssql,
+    Jsonl,
+    Csv,
+}
+
+/// A single level as it comes out of upstream, before any normalization. The
+/// field names and types mirror the columns of Cryptan's `v_Uncleared` view,
+/// because that's what everything else is modelled after. File-based sources
+/// use the same field names, with dates formatted as `2024-06-15 14:31:13`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct RawSmm2Level {
+    pub id: Option<String>,
+    #[serde(default, with = "upstream_datetime::option")]
+    pub date: Option<PrimitiveDateTime>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub upload_time: Option<i64>,
+    pub attempts: Option<i64>,
+    pub footprints: Option<i64>,
+    pub likes: Option<String>,
+    pub boos: Option<String>,
+    pub comments: Option<String>,
+    pub clear_condition: Option<i64>,
+    pub clear_condition_magnitude: Option<i64>,
+    pub style: Option<String>,
+    pub theme: Option<String>,
+    pub tag1: Option<String>,
+    pub tag2: Option<String>,
+}
+
+/// A single SMM1 level as it comes out of a file-based source. There is no
+/// upstream database for SMM1 anymore, so this just uses the same naming as
+/// [RawSmm2Level] for the fields both games have in common.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct RawSmm1Level {
+    pub id: Option<String>,
+    #[serde(default, with = "upstream_datetime::option")]
+    pub date: Option<PrimitiveDateTime>,
+    pub name: Option<String>,
+    pub attempts: Option<i64>,
+    pub footprints: Option<i64>,
+    pub likes: Option<i64>,
+    pub style: Option<String>,
+}
+
+/// A single row from an [ImportSource]. Rows that can't even be read into a
+/// [RawSmm2Level] (or whatever raw level type is read) end up in the
+/// quarantine right away.
+pub type SourceRow<T = RawSmm2Level> = Result<T, QuarantinedLevel>;
+
+/// Something the importer can get a full set of uncleared levels from, as
+/// raw levels of type `T`.
+pub trait ImportSource<T = RawSmm2Level> {
+    /// A short name for the source, stored with every import run.
+    fn name(&self) -> &'static str;
+
+    /// Fetches all currently uncleared levels. Broken rows should be returned
+    /// as errors instead of failing the whole fetch.
+    fn fetch_levels(&mut self) -> impl Future<Output = anyhow::Result<Vec<SourceRow<T>>>> + Send;
+}
+
+/// Extracts the level ID from a raw JSON row, if there is one.
+fn raw_level_id(raw: &serde_json::Value) -> Option<String> {
+    raw.get("id")
+        .and_then(|id| id.as_str())
+        .map(|id| id.to_owned())
+}
@@ -0,0 +1,107 @@
+use std::{io::Read, path::PathBuf};
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    components::import_source::{ImportSource, SourceRow, raw_level_id},
+    entities::quarantined_level::QuarantinedLevel,
+};
+
+/// Reads levels from a CSV file. The file needs a header row with the field
+/// names of the raw level type, like
+/// [crate::components::import_source::RawSmm2Level], in any order.
+/// Empty cells are treated as NULL.
+pub struct CsvSource {
+    path: PathBuf,
+}
+
+impl CsvSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Parses the CSV content. Only a broken header fails the whole thing,
+    /// broken records get quarantined individually.
+    pub fn parse<T: DeserializeOwned, R: Read>(reader: R) -> anyhow::Result<Vec<SourceRow<T>>> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let headers = reader.headers()?.clone();
+
+        let mut rows = Vec::new();
+        for (index, record) in reader.records().enumerate() {
+            let record = record.with_context(|| format!("could not read record {}", index + 1))?;
+            rows.push(record.deserialize(Some(&headers)).map_err(|err| {
+                let raw = serde_json::Value::Object(
+                    headers
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(k, v)| (k.to_owned(), serde_json::Value::String(v.to_owned())))
+                        .collect(),
+                );
+                QuarantinedLevel {
+                    level_id: raw_level_id(&raw),
+                    raw,
+                    error: format!("invalid level in record {}: {err}", index + 1),
+                }
+            }));
+        }
+
+        Ok(rows)
+    }
+
+    /// Reads and parses the whole file into raw levels of any kind.
+    pub async fn read<T: DeserializeOwned + Send + 'static>(
+        &self,
+    ) -> anyhow::Result<Vec<SourceRow<T>>> {
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("could not read `{}`", path.display()))?;
+            Self::parse(file)
+        })
+        .await?
+    }
+}
+
+impl<T: DeserializeOwned + Send + 'static> ImportSource<T> for CsvSource {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    async fn fetch_levels(&mut self) -> anyhow::Result<Vec<SourceRow<T>>> {
+        self.read().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::components::import_source::RawSmm2Level;
+
+    #[test]
+    fn parse_reads_levels_with_empty_cells_as_none() {
+        let content = "\
+id,date,name,description,attempts,likes,clear_condition,tag1,tag2
+000-FBT-9KF,2024-06-15 14:31:13.250,\"Level, with comma\",,14,0,,Speedrun,Technical
+000k0r5ng,yesterday,Level,,14,0,,,
+";
+
+        let rows = CsvSource::parse::<RawSmm2Level, _>(content.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        let level = rows[0].as_ref().unwrap();
+        assert_eq!(level.id.as_deref(), Some("000-FBT-9KF"));
+        assert_eq!(level.date, Some(datetime!(2024-06-15 14:31:13.250)));
+        assert_eq!(level.name.as_deref(), Some("Level, with comma"));
+        assert_eq!(level.description, None);
+        assert_eq!(level.attempts, Some(14));
+        assert_eq!(level.clear_condition, None);
+        assert_eq!(level.tag2.as_deref(), Some("Technical"));
+
+        let quarantined = rows[1].as_ref().unwrap_err();
+        assert_eq!(quarantined.level_id.as_deref(), Some("000k0r5ng"));
+        assert_eq!(quarantined.raw["date"], "yesterday");
+    }
+}
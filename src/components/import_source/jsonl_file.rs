@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    components::import_source::{ImportSource, SourceRow, raw_level_id},
+    entities::quarantined_level::QuarantinedLevel,
+};
+
+/// Reads levels from a JSON Lines file, with one raw level object per line.
+/// Empty lines are ignored.
+pub struct JsonlSource {
+    path: PathBuf,
+}
+
+impl JsonlSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn parse<T: DeserializeOwned>(content: &str) -> Vec<SourceRow<T>> {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str::<T>(line).map_err(|err| {
+                    let raw = serde_json::from_str(line)
+                        .unwrap_or_else(|_| serde_json::Value::String(line.to_owned()));
+                    QuarantinedLevel {
+                        level_id: raw_level_id(&raw),
+                        raw,
+                        error: format!("invalid level on line {}: {err}", index + 1),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Reads and parses the whole file into raw levels of any kind.
+    pub async fn read<T: DeserializeOwned>(&self) -> anyhow::Result<Vec<SourceRow<T>>> {
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .with_context(|| format!("could not read `{}`", self.path.display()))?;
+        Ok(Self::parse(&content))
+    }
+}
+
+impl<T: DeserializeOwned + Send> ImportSource<T> for JsonlSource {
+    fn name(&self) -> &'static str {
+        "jsonl"
+    }
+
+    async fn fetch_levels(&mut self) -> anyhow::Result<Vec<SourceRow<T>>> {
+        self.read().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::components::import_source::RawSmm2Level;
+
+    #[test]
+    fn parse_reads_levels_and_skips_empty_lines() {
+        let content = r#"{"id":"000-FBT-9KF","date":"2024-06-15 14:31:13","name":"Level","attempts":14,"likes":"0"}
+
+{"id":"000k0r5ng","tag1":"Speedrun","tag2":null}
+{"id":"000rw9nng","attempts":"lots"}
+"#;
+
+        let rows = JsonlSource::parse::<RawSmm2Level>(content);
+        assert_eq!(rows.len(), 3);
+
+        let level = rows[0].as_ref().unwrap();
+        assert_eq!(level.id.as_deref(), Some("000-FBT-9KF"));
+        assert_eq!(level.date, Some(datetime!(2024-06-15 14:31:13)));
+        assert_eq!(level.attempts, Some(14));
+        assert_eq!(level.likes.as_deref(), Some("0"));
+
+        let level = rows[1].as_ref().unwrap();
+        assert_eq!(level.date, None);
+        assert_eq!(level.tag1.as_deref(), Some("Speedrun"));
+
+        let quarantined = rows[2].as_ref().unwrap_err();
+        assert_eq!(quarantined.level_id.as_deref(), Some("000rw9nng"));
+        assert!(quarantined.error.starts_with("invalid level on line 4"));
+    }
+}
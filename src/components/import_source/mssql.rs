@@ -0,0 +1,108 @@
+use futures_util::TryStreamExt;
+use tokio::net::TcpStream;
+use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
+
+use crate::{
+    components::import_source::{ImportSource, RawSmm1Level, RawSmm2Level, SourceRow},
+    entities::quarantined_level::QuarantinedLevel,
+};
+
+/// Reads levels from Cryptan's MSSQL database, via the `v_Uncleared` view.
+pub struct MssqlSource {
+    client: tiberius::Client<Compat<TcpStream>>,
+}
+
+impl MssqlSource {
+    pub async fn connect(connstring: &str) -> anyhow::Result<Self> {
+        let config = tiberius::Config::from_ado_string(connstring)?;
+        let tcp = TcpStream::connect(config.get_addr()).await?;
+        tcp.set_nodelay(true)?;
+        let client = tiberius::Client::connect(config, tcp.compat_write()).await?;
+
+        Ok(Self { client })
+    }
+}
+
+impl ImportSource for MssqlSource {
+    fn name(&self) -> &'static str {
+        "mssql"
+    }
+
+    async fn fetch_levels(&mut self) -> anyhow::Result<Vec<SourceRow>> {
+        let mut levels = Vec::new();
+        let mut rows = self
+            .client
+            .simple_query("SELECT * FROM v_Uncleared")
+            .await?
+            .into_row_stream();
+        while let Some(row) = rows.try_next().await? {
+            levels.push(read_row(&row).map_err(|err| quarantine_row(&row, err)));
+        }
+
+        Ok(levels)
+    }
+}
+
+/// There is no upstream database for SMM1 anymore, so those levels can only
+/// be imported from files.
+impl ImportSource<RawSmm1Level> for MssqlSource {
+    fn name(&self) -> &'static str {
+        "mssql"
+    }
+
+    async fn fetch_levels(&mut self) -> anyhow::Result<Vec<SourceRow<RawSmm1Level>>> {
+        anyhow::bail!("SMM1 levels can only be imported from jsonl or csv files!")
+    }
+}
+
+fn read_row(row: &tiberius::Row) -> Result<RawSmm2Level, tiberius::error::Error> {
+    let owned_str = |field: &str| {
+        row.try_get::<&str, &str>(field)
+            .map(|s| s.map(|s| s.to_owned()))
+    };
+    let int = |field: &str| row.try_get::<i32, &str>(field).map(|i| i.map(i64::from));
+
+    Ok(RawSmm2Level {
+        id: owned_str("id")?,
+        date: row.try_get("date")?,
+        name: owned_str("name")?,
+        description: owned_str("description")?,
+        upload_time: int("upload_time")?,
+        attempts: int("attempts")?,
+        footprints: int("footprints")?,
+        likes: owned_str("likes")?,
+        boos: owned_str("boos")?,
+        comments: owned_str("comments")?,
+        clear_condition: int("clear_condition")?,
+        clear_condition_magnitude: int("clear_condition_magnitude")?,
+        style: owned_str("style")?,
+        theme: owned_str("theme")?,
+        tag1: owned_str("tag1")?,
+        tag2: owned_str("tag2")?,
+    })
+}
+
+/// There's no nice way to serialize a [tiberius::Row], so the quarantine gets
+/// the debug representation of each cell. That's good enough to figure out
+/// what's wrong.
+fn quarantine_row(row: &tiberius::Row, err: tiberius::error::Error) -> QuarantinedLevel {
+    let raw = row
+        .cells()
+        .map(|(column, data)| {
+            (
+                column.name().to_owned(),
+                serde_json::Value::String(format!("{data:?}")),
+            )
+        })
+        .collect();
+
+    QuarantinedLevel {
+        level_id: row
+            .try_get::<&str, &str>("id")
+            .ok()
+            .flatten()
+            .map(|id| id.to_owned()),
+        raw: serde_json::Value::Object(raw),
+        error: err.to_string(),
+    }
+}
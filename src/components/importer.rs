@@ -0,0 +1,829 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Display, Formatter},
+    future::Future,
+};
+
+use anyhow::bail;
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool, postgres::PgQueryResult};
+use time::{Duration, OffsetDateTime};
+use tracing::{error, info, warn};
+
+use crate::{
+    components::{import_source::SourceRow, invalidation::InvalidationEvent, settings::Settings},
+    entities::{
+        import_run::{ImportOutcome, ImportRun},
+        level::{Game, Level},
+        level_archive::RemovalReason,
+        quarantined_level::QuarantinedLevel,
+    },
+};
+
+/// Everything that can go wrong when turning a raw upstream row into a level.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum LevelConversionError {
+    #[error("`{0}` is NULL")]
+    MissingField(&'static str),
+
+    #[error("`{field}` is not a number: `{value}`")]
+    InvalidNumber { field: &'static str, value: String },
+}
+
+/// A [Level] the importer can diff against a fresh set of levels from
+/// upstream. The conversion from [Self::Raw] happens through [TryFrom], rows
+/// that fail to convert end up in the quarantine.
+pub trait ImportableLevel: Level + Clone + PartialEq
+where
+    Self: for<'r> TryFrom<&'r Self::Raw, Error = LevelConversionError>,
+{
+    /// The level as it comes out of an import source.
+    type Raw: Serialize;
+
+    /// The level ID of a raw row, if it has one, as it comes from upstream.
+    fn raw_level_id(raw: &Self::Raw) -> Option<&str>;
+
+    fn id(&self) -> &str;
+    fn year(&self) -> i64;
+    fn style(&self) -> &str;
+
+    /// Returns all levels currently in the randomizer, hidden ones included.
+    fn get_all(
+        conn: &mut PgConnection,
+    ) -> impl Future<Output = Result<Vec<Self>, sqlx::Error>> + Send;
+
+    /// Updates a whole batch of existing levels.
+    fn update_many(
+        conn: &mut PgConnection,
+        levels: &[Self],
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send;
+
+    /// Brings back all levels hidden by [Level::hide_pending_clear] that were
+    /// still listed as uncleared by an import that fetched its levels at
+    /// `fetched_at`.
+    fn reveal_pending_clears(
+        conn: &mut PgConnection,
+        fetched_at: OffsetDateTime,
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send;
+}
+
+fn quarantine<L: ImportableLevel>(raw: &L::Raw, err: LevelConversionError) -> QuarantinedLevel {
+    QuarantinedLevel {
+        level_id: L::raw_level_id(raw).map(|id| id.to_owned()),
+        raw: serde_json::to_value(raw).unwrap_or_default(),
+        error: err.to_string(),
+    }
+}
+
+/// A short summary of what an import changed, mostly used for logging.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    pub skipped_blocklisted: usize,
+    pub quarantined: usize,
+}
+
+/// Knobs for a single import run.
+#[derive(Clone, Debug)]
+pub struct ImportOptions {
+    /// Runs the full import, but rolls back the transaction at the end.
+    pub dry_run: bool,
+
+    /// Imports even if one of the [ImportGuards] is violated.
+    pub force: bool,
+
+    pub guards: ImportGuards,
+
+    /// How many levels are written to the database per query.
+    pub batch_size: usize,
+
+    /// For how long levels archived ahead of upstream are skipped, see
+    /// [Game::archived_ahead_of_upstream_ids].
+    pub archive_hold: Duration,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            force: false,
+            guards: ImportGuards::default(),
+            batch_size: 1000,
+            archive_hold: Duration::weeks(1),
+        }
+    }
+}
+
+/// Years with fewer levels than this are ignored by the per-year drop guard.
+/// Once a year only has a handful of levels left, losing most of them in one
+/// import is just people doing their job, and not a broken upstream.
+const MIN_YEAR_SIZE_FOR_DROP_GUARD: usize = 100;
+
+/// Sanity checks that protect us from committing a broken upstream dataset,
+/// like a view refresh that only returns a fraction of the levels.
+#[derive(Clone, Debug)]
+pub struct ImportGuards {
+    pub min_levels: usize,
+    pub max_drop_percent: f64,
+    pub max_year_drop_percent: f64,
+}
+
+impl Default for ImportGuards {
+    fn default() -> Self {
+        Self {
+            min_levels: 1,
+            max_drop_percent: 100.0,
+            max_year_drop_percent: 100.0,
+        }
+    }
+}
+
+impl From<&Settings> for ImportGuards {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            min_levels: settings.import_min_levels,
+            max_drop_percent: settings.import_max_drop_percent,
+            max_year_drop_percent: settings.import_max_year_drop_percent,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum GuardViolation {
+    #[error("upstream only has {count} levels, expected at least {min}")]
+    TooFewLevels { count: usize, min: usize },
+
+    #[error("level count would drop by {percent:.1}% ({before} -> {after}), allowed are {max}%")]
+    TotalDrop {
+        before: usize,
+        after: usize,
+        percent: f64,
+        max: f64,
+    },
+
+    #[error(
+        "level count for {year} would drop by {percent:.1}% ({before} -> {after}), allowed are {max}%"
+    )]
+    YearDrop {
+        year: i64,
+        before: usize,
+        after: usize,
+        percent: f64,
+        max: f64,
+    },
+}
+
+impl ImportGuards {
+    /// Checks the level counts per year before and after the import against
+    /// the configured limits, and returns all violations.
+    pub fn check(
+        &self,
+        before: &BTreeMap<i64, usize>,
+        after: &BTreeMap<i64, usize>,
+    ) -> Vec<GuardViolation> {
+        let mut violations = Vec::new();
+
+        let total_before: usize = before.values().sum();
+        let total_after: usize = after.values().sum();
+        if total_after < self.min_levels {
+            violations.push(GuardViolation::TooFewLevels {
+                count: total_after,
+                min: self.min_levels,
+            });
+        }
+
+        let percent = drop_percent(total_before, total_after);
+        if percent > self.max_drop_percent {
+            violations.push(GuardViolation::TotalDrop {
+                before: total_before,
+                after: total_after,
+                percent,
+                max: self.max_drop_percent,
+            });
+        }
+
+        for (year, year_before) in before {
+            if *year_before < MIN_YEAR_SIZE_FOR_DROP_GUARD {
+                continue;
+            }
+
+            let year_after = after.get(year).copied().unwrap_or_default();
+            let percent = drop_percent(*year_before, year_after);
+            if percent > self.max_year_drop_percent {
+                violations.push(GuardViolation::YearDrop {
+                    year: *year,
+                    before: *year_before,
+                    after: year_after,
+                    percent,
+                    max: self.max_year_drop_percent,
+                });
+            }
+        }
+
+        violations
+    }
+}
+
+fn drop_percent(before: usize, after: usize) -> f64 {
+    if before == 0 || after >= before {
+        return 0.0;
+    }
+
+    (before - after) as f64 / before as f64 * 100.0
+}
+
+/// Counts levels per year, counting each level ID only once.
+fn count_unique_by_year<'a, L: ImportableLevel + 'a>(
+    levels: impl Iterator<Item = &'a L>,
+) -> BTreeMap<i64, usize> {
+    let mut seen_ids = HashSet::new();
+    let mut counts = BTreeMap::new();
+    for level in levels {
+        if seen_ids.insert(level.id()) {
+            *counts.entry(level.year()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// The delta between the levels currently stored for a game and a fresh set
+/// of levels from upstream. Levels that are gone from upstream are levels
+/// that got cleared, so [Self::removed] is the interesting bit here.
+#[derive(Debug)]
+pub struct LevelDiff<L> {
+    pub added: Vec<L>,
+    pub updated: Vec<L>,
+    pub removed: Vec<L>,
+    pub unchanged: usize,
+}
+
+impl<L> Default for LevelDiff<L> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            updated: Vec::new(),
+            removed: Vec::new(),
+            unchanged: 0,
+        }
+    }
+}
+
+impl<L: ImportableLevel> LevelDiff<L> {
+    /// Computes the diff between the `existing` levels and the `upstream`
+    /// levels. If upstream contains the same ID more than once, the first
+    /// occurrence wins, mirroring the old `ON CONFLICT DO NOTHING` behavior.
+    pub fn compute(mut existing: HashMap<String, L>, upstream: Vec<L>) -> Self {
+        let mut diff = Self::default();
+        let mut seen_ids = HashSet::new();
+
+        for level in upstream {
+            if !seen_ids.insert(level.id().to_owned()) {
+                continue;
+            }
+
+            match existing.remove(level.id()) {
+                None => diff.added.push(level),
+                Some(current) if current != level => diff.updated.push(level),
+                Some(_) => diff.unchanged += 1,
+            }
+        }
+
+        diff.removed = existing.into_values().collect();
+        diff.removed.sort_by(|a, b| a.id().cmp(b.id()));
+        diff
+    }
+
+    /// Keeps currently stored levels with the given IDs, even if they are
+    /// missing from upstream. Used for quarantined levels, where a broken row
+    /// does not mean that the level got cleared.
+    pub fn keep(&mut self, level_ids: &HashSet<String>) {
+        let before = self.removed.len();
+        self.removed.retain(|level| !level_ids.contains(level.id()));
+        self.unchanged += before - self.removed.len();
+    }
+
+    pub fn removed_ids(&self) -> Vec<String> {
+        self.removed
+            .iter()
+            .map(|level| level.id().to_owned())
+            .collect()
+    }
+
+    pub fn summary(&self) -> ImportSummary {
+        ImportSummary {
+            added: self.added.len(),
+            updated: self.updated.len(),
+            removed: self.removed.len(),
+            unchanged: self.unchanged,
+            skipped_blocklisted: 0,
+            quarantined: 0,
+        }
+    }
+}
+
+/// Everything worth knowing about what an import changed - or, in a dry run,
+/// what it would have changed. Serializes into the JSON report, and the
+/// [std::fmt::Display] impl is the human-readable version of the same thing.
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub game: Game,
+    pub dry_run: bool,
+    pub outcome: ImportOutcome,
+    pub summary: ImportSummary,
+    pub guard_violations: Vec<String>,
+    pub added_by_year: BTreeMap<i64, usize>,
+    pub added_by_style: BTreeMap<String, usize>,
+    pub removed_by_year: BTreeMap<i64, usize>,
+    pub removed_by_style: BTreeMap<String, usize>,
+    pub added_ids: Vec<String>,
+    pub removed_ids: Vec<String>,
+    pub skipped_blocklisted_ids: Vec<String>,
+    pub quarantined_levels: Vec<QuarantinedLevel>,
+}
+
+impl ImportReport {
+    pub fn new<L: ImportableLevel>(
+        diff: &LevelDiff<L>,
+        mut skipped_blocklisted_ids: Vec<String>,
+        quarantined_levels: Vec<QuarantinedLevel>,
+        guard_violations: &[GuardViolation],
+        options: &ImportOptions,
+        outcome: ImportOutcome,
+    ) -> Self {
+        skipped_blocklisted_ids.sort();
+
+        Self {
+            game: L::GAME,
+            dry_run: options.dry_run,
+            outcome,
+            summary: ImportSummary {
+                skipped_blocklisted: skipped_blocklisted_ids.len(),
+                quarantined: quarantined_levels.len(),
+                ..diff.summary()
+            },
+            guard_violations: guard_violations.iter().map(|v| v.to_string()).collect(),
+            added_by_year: count_by(&diff.added, |level| level.year()),
+            added_by_style: count_by(&diff.added, |level| level.style().to_owned()),
+            removed_by_year: count_by(&diff.removed, |level| level.year()),
+            removed_by_style: count_by(&diff.removed, |level| level.style().to_owned()),
+            added_ids: diff
+                .added
+                .iter()
+                .map(|level| level.id().to_owned())
+                .collect(),
+            removed_ids: diff.removed_ids(),
+            skipped_blocklisted_ids,
+            quarantined_levels,
+        }
+    }
+}
+
+fn count_by<L, K: Ord>(levels: &[L], key: impl Fn(&L) -> K) -> BTreeMap<K, usize> {
+    let mut counts = BTreeMap::new();
+    for level in levels {
+        *counts.entry(key(level)).or_default() += 1;
+    }
+    counts
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let title = match self.dry_run {
+            true => "Import report (dry run, nothing was changed)",
+            false => "Import report",
+        };
+        writeln!(f, "{title}")?;
+        writeln!(f, "  game: {}", self.game)?;
+        writeln!(f, "  outcome: {:?}", self.outcome)?;
+        writeln!(
+            f,
+            "  {} added, {} updated, {} removed, {} unchanged, {} skipped by blocklist, {} quarantined",
+            self.summary.added,
+            self.summary.updated,
+            self.summary.removed,
+            self.summary.unchanged,
+            self.summary.skipped_blocklisted,
+            self.summary.quarantined
+        )?;
+        for violation in &self.guard_violations {
+            writeln!(f, "  guard violation: {violation}")?;
+        }
+
+        for (label, counts) in [
+            ("Removed levels by year", &self.removed_by_year),
+            ("Added levels by year", &self.added_by_year),
+        ] {
+            writeln!(f, "\n{label}:")?;
+            if counts.is_empty() {
+                writeln!(f, "  (none)")?;
+            }
+            for (year, count) in counts {
+                writeln!(f, "  {year}: {count}")?;
+            }
+        }
+
+        for (label, counts) in [
+            ("Removed levels by style", &self.removed_by_style),
+            ("Added levels by style", &self.added_by_style),
+        ] {
+            writeln!(f, "\n{label}:")?;
+            if counts.is_empty() {
+                writeln!(f, "  (none)")?;
+            }
+            for (style, count) in counts {
+                writeln!(f, "  {style}: {count}")?;
+            }
+        }
+
+        for (label, ids) in [
+            ("Added levels", &self.added_ids),
+            ("Skipped blocklisted levels", &self.skipped_blocklisted_ids),
+        ] {
+            writeln!(f, "\n{label}:")?;
+            if ids.is_empty() {
+                writeln!(f, "  (none)")?;
+            }
+            for id in ids {
+                writeln!(f, "  {}", self.game.formatted_level_id(id))?;
+            }
+        }
+
+        writeln!(f, "\nQuarantined levels:")?;
+        if self.quarantined_levels.is_empty() {
+            writeln!(f, "  (none)")?;
+        }
+        for level in &self.quarantined_levels {
+            writeln!(
+                f,
+                "  {}: {}",
+                level.level_id.as_deref().unwrap_or("(no id)"),
+                level.error
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs a full import of the rows `fetch_rows` resolves to, and records it in
+/// `import_runs`. The run is recorded outside of the import transaction, so
+/// failed and rolled back imports show up in the history as well. Dry runs are
+/// not recorded, as they don't change anything.
+#[tracing::instrument(skip(fetch_rows, own_db, options), fields(game = L::GAME.as_str(), dry_run = options.dry_run))]
+pub async fn run<L: ImportableLevel>(
+    source_name: &str,
+    fetch_rows: impl Future<Output = anyhow::Result<Vec<SourceRow<L::Raw>>>>,
+    own_db: PgPool,
+    options: &ImportOptions,
+) -> anyhow::Result<ImportReport> {
+    if options.dry_run {
+        return import_levels::<L>(fetch_rows, &own_db, None, options).await;
+    }
+
+    let import_run = ImportRun::start(&own_db, L::GAME, source_name).await?;
+
+    match import_levels::<L>(fetch_rows, &own_db, Some(&import_run), options).await {
+        Ok(report) => {
+            import_run
+                .finish(&own_db, report.outcome, &report.summary, None)
+                .await?;
+            Ok(report)
+        }
+        Err(err) => {
+            if let Err(finish_err) = import_run
+                .finish(
+                    &own_db,
+                    ImportOutcome::RolledBack,
+                    &ImportSummary::default(),
+                    Some(&err.to_string()),
+                )
+                .await
+            {
+                error!("could not record failed import run: {finish_err:?}");
+            }
+            Err(err)
+        }
+    }
+}
+
+async fn import_levels<L: ImportableLevel>(
+    fetch_rows: impl Future<Output = anyhow::Result<Vec<SourceRow<L::Raw>>>>,
+    own_db: &PgPool,
+    import_run: Option<&ImportRun>,
+    options: &ImportOptions,
+) -> anyhow::Result<ImportReport> {
+    let level_blocklist = L::blocklisted_ids(own_db).await?;
+    let archived_ids = L::GAME
+        .archived_ahead_of_upstream_ids(own_db, OffsetDateTime::now_utc() - options.archive_hold)
+        .await?;
+
+    info!("fetching levels...");
+    let fetched_at = OffsetDateTime::now_utc();
+    let mut upstream_levels = Vec::new();
+    let mut skipped_blocklisted_ids = Vec::new();
+    let mut skipped_archived = 0;
+    let mut quarantined_levels = Vec::new();
+    for row in fetch_rows.await? {
+        let level = row.and_then(|raw| L::try_from(&raw).map_err(|e| quarantine::<L>(&raw, e)));
+        let level = match level {
+            Ok(level) => level,
+            Err(quarantined) => {
+                warn!(
+                    "quarantining level {:?}: {}",
+                    quarantined.level_id, quarantined.error
+                );
+                quarantined_levels.push(quarantined);
+                continue;
+            }
+        };
+
+        if level_blocklist.contains(level.id()) {
+            skipped_blocklisted_ids.push(level.id().to_owned());
+            continue;
+        }
+        if archived_ids.contains(level.id()) {
+            skipped_archived += 1;
+            continue;
+        }
+
+        upstream_levels.push(level);
+    }
+
+    if skipped_archived > 0 {
+        info!(
+            "skipped {skipped_archived} level(s) that were verified as cleared or removed by hand"
+        );
+    }
+
+    if let Some(import_run) = import_run
+        && !quarantined_levels.is_empty()
+    {
+        QuarantinedLevel::store_many(own_db, import_run.id, &quarantined_levels).await?;
+    }
+    let quarantined_ids: HashSet<String> = quarantined_levels
+        .iter()
+        .filter_map(|level| level.level_id.as_deref())
+        .map(L::normalized_internal_level_id)
+        .collect();
+
+    let mut db_transaction = own_db.begin().await?;
+
+    info!("diffing against current levels...");
+    let existing_levels: HashMap<String, L> = L::get_all(&mut db_transaction)
+        .await?
+        .into_iter()
+        .map(|level| (level.id().to_owned(), level))
+        .collect();
+
+    let guard_violations = options.guards.check(
+        &count_unique_by_year(existing_levels.values()),
+        &count_unique_by_year(upstream_levels.iter()),
+    );
+    for violation in &guard_violations {
+        warn!("import guard violated: {violation}");
+    }
+    if !guard_violations.is_empty() && !options.force && !options.dry_run {
+        db_transaction.rollback().await?;
+        bail!(
+            "import aborted, {} import guard(s) violated: {}. Use --force to import anyway.",
+            guard_violations.len(),
+            guard_violations
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<String>>()
+                .join("; ")
+        );
+    }
+
+    let mut diff = LevelDiff::compute(existing_levels, upstream_levels);
+    diff.keep(&quarantined_ids);
+
+    info!("applying diff...");
+    let (blocklisted_ids, cleared_ids): (Vec<String>, Vec<String>) = diff
+        .removed_ids()
+        .into_iter()
+        .partition(|id| level_blocklist.contains(id));
+    L::archive_many(
+        &mut db_transaction,
+        &cleared_ids,
+        RemovalReason::UpstreamCleared,
+    )
+    .await?;
+    L::archive_many(
+        &mut db_transaction,
+        &blocklisted_ids,
+        RemovalReason::Blocklisted,
+    )
+    .await?;
+    for batch in diff.updated.chunks(options.batch_size.max(1)) {
+        L::update_many(&mut db_transaction, batch).await?;
+    }
+    for batch in diff.added.chunks(options.batch_size.max(1)) {
+        L::store_many(&mut *db_transaction, batch).await?;
+    }
+    let revealed = L::reveal_pending_clears(&mut db_transaction, fetched_at).await?;
+    info!(
+        "{} level(s) reported as cleared are still uncleared upstream",
+        revealed.rows_affected()
+    );
+
+    let outcome = if options.dry_run {
+        info!("dry run, rolling back...");
+        db_transaction.rollback().await?;
+        ImportOutcome::RolledBack
+    } else {
+        info!("committing...");
+        InvalidationEvent::LevelsImported { game: L::GAME }
+            .publish(&mut *db_transaction)
+            .await?;
+        db_transaction.commit().await?;
+        ImportOutcome::Committed
+    };
+    info!("done!");
+
+    Ok(ImportReport::new(
+        &diff,
+        skipped_blocklisted_ids,
+        quarantined_levels,
+        &guard_violations,
+        options,
+        outcome,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::entities::smm2_level::Smm2Level;
+
+    fn level(id: &str, attempts: i64) -> Smm2Level {
+        Smm2Level {
+            id: id.to_owned(),
+            year: 2024,
+            title: "Test Level".to_owned(),
+            description: None,
+            uploaded_at: datetime!(2024-06-15 14:31:13 UTC),
+            clearcheck_ms: 19233,
+            attempts,
+            footprints: 5,
+            likes: 0,
+            boos: 0,
+            comments: 0,
+            clear_condition: None,
+            clear_condition_magnitude: None,
+            style: "NSMBU".to_owned(),
+            theme: "sky".to_owned(),
+            tags: vec!["speedrun".to_owned()],
+        }
+    }
+
+    #[test]
+    fn level_diff_sorts_levels_into_buckets() {
+        let existing = [level("aaa", 1), level("bbb", 1), level("ccc", 1)]
+            .into_iter()
+            .map(|l| (l.id.clone(), l))
+            .collect();
+        let upstream = vec![level("bbb", 1), level("ccc", 2), level("ddd", 1)];
+
+        let diff = LevelDiff::compute(existing, upstream);
+        assert_eq!(diff.added, vec![level("ddd", 1)]);
+        assert_eq!(diff.updated, vec![level("ccc", 2)]);
+        assert_eq!(diff.removed, vec![level("aaa", 1)]);
+        assert_eq!(
+            diff.summary(),
+            ImportSummary {
+                added: 1,
+                updated: 1,
+                removed: 1,
+                unchanged: 1,
+                skipped_blocklisted: 0,
+                quarantined: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn level_diff_keep_retains_removed_levels() {
+        let existing = [level("aaa", 1), level("bbb", 1)]
+            .into_iter()
+            .map(|l| (l.id.clone(), l))
+            .collect();
+
+        let mut diff = LevelDiff::compute(existing, vec![]);
+        diff.keep(&HashSet::from(["aaa".to_owned()]));
+        assert_eq!(diff.removed, vec![level("bbb", 1)]);
+        assert_eq!(diff.unchanged, 1);
+    }
+
+    #[test]
+    fn level_diff_keeps_first_duplicate() {
+        let upstream = vec![level("aaa", 1), level("aaa", 2)];
+
+        let diff = LevelDiff::compute(HashMap::new(), upstream);
+        assert_eq!(diff.added, vec![level("aaa", 1)]);
+    }
+
+    #[test]
+    fn import_report_groups_by_year_and_style() {
+        let mut old_level = level("aaa", 1);
+        old_level.year = 2023;
+        old_level.style = "SMW".to_owned();
+        let existing = [old_level, level("bbb", 1), level("ccc", 1)]
+            .into_iter()
+            .map(|l| (l.id.clone(), l))
+            .collect();
+        let upstream = vec![level("ddd", 1)];
+
+        let report = ImportReport::new(
+            &LevelDiff::compute(existing, upstream),
+            vec!["zzz".to_owned(), "yyy".to_owned()],
+            vec![],
+            &[],
+            &ImportOptions {
+                dry_run: true,
+                ..Default::default()
+            },
+            ImportOutcome::RolledBack,
+        );
+        assert_eq!(
+            report.removed_by_year,
+            BTreeMap::from([(2023, 1), (2024, 2)])
+        );
+        assert_eq!(
+            report.removed_by_style,
+            BTreeMap::from([("NSMBU".to_owned(), 2), ("SMW".to_owned(), 1)])
+        );
+        assert_eq!(report.added_ids, vec!["ddd".to_owned()]);
+        assert_eq!(report.skipped_blocklisted_ids, vec!["yyy", "zzz"]);
+        assert_eq!(report.summary.skipped_blocklisted, 2);
+    }
+
+    #[test]
+    fn import_guards_pass_on_small_changes() {
+        let guards = ImportGuards {
+            min_levels: 10,
+            max_drop_percent: 10.0,
+            max_year_drop_percent: 20.0,
+        };
+        let before = BTreeMap::from([(2023, 500), (2024, 500)]);
+        let after = BTreeMap::from([(2023, 450), (2024, 490)]);
+
+        assert_eq!(guards.check(&before, &after), vec![]);
+    }
+
+    #[test]
+    fn import_guards_reject_empty_upstream() {
+        let before = BTreeMap::from([(2023, 5)]);
+
+        assert_eq!(
+            ImportGuards::default().check(&before, &BTreeMap::new()),
+            vec![GuardViolation::TooFewLevels { count: 0, min: 1 }]
+        );
+    }
+
+    #[test]
+    fn import_guards_catch_drops_and_tiny_imports() {
+        let guards = ImportGuards {
+            min_levels: 500,
+            max_drop_percent: 25.0,
+            max_year_drop_percent: 50.0,
+        };
+        let before = BTreeMap::from([(2022, 10), (2023, 500), (2024, 500)]);
+        let after = BTreeMap::from([(2022, 1), (2023, 200), (2024, 100)]);
+
+        assert_eq!(
+            guards.check(&before, &after),
+            vec![
+                GuardViolation::TooFewLevels {
+                    count: 301,
+                    min: 500
+                },
+                GuardViolation::TotalDrop {
+                    before: 1010,
+                    after: 301,
+                    percent: 709.0 / 1010.0 * 100.0,
+                    max: 25.0
+                },
+                GuardViolation::YearDrop {
+                    year: 2023,
+                    before: 500,
+                    after: 200,
+                    percent: 60.0,
+                    max: 50.0
+                },
+                GuardViolation::YearDrop {
+                    year: 2024,
+                    before: 500,
+                    after: 100,
+                    percent: 80.0,
+                    max: 50.0
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    PgExecutor, PgPool,
+    postgres::{PgListener, PgPoolOptions},
+};
+use time::{Duration, OffsetDateTime};
+use tracing::{error, info, warn};
+
+use crate::{components::app_state::AppState, entities::level::Game};
+
+/// The Postgres channel all [InvalidationEvent]s are sent on.
+pub const CHANNEL: &str = "smm_zerop_invalidation";
+
+const RECONNECT_BASE_DELAY: Duration = Duration::seconds(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::minutes(1);
+
+/// Something changed that caches of level data might care about. Events are
+/// sent with `NOTIFY`, so every web server hears about them, no matter which
+/// process made the change.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum InvalidationEvent {
+    /// An import was committed.
+    LevelsImported { game: Game },
+    /// A level was added to or removed from the blocklist.
+    BlocklistChanged { game: Game, level_id: String },
+    /// A level was reported as cleared, and is hidden from the randomizer
+    /// until `until`.
+    LevelHidden {
+        game: Game,
+        level_id: String,
+        #[serde(with = "time::serde::rfc3339")]
+        until: OffsetDateTime,
+    },
+    /// A level was archived outside of an import, because its clear was
+    /// verified or someone removed it by hand.
+    LevelArchived { game: Game, level_id: String },
+    /// An archived level was put back into the randomizer by hand.
+    LevelRestored { game: Game, level_id: String },
+    /// Never published. Subscribers get this after the listener lost its
+    /// connection, since they might have missed events in the meantime.
+    #[serde(skip)]
+    Resync,
+}
+
+impl InvalidationEvent {
+    /// Sends the event to all listeners. If `executor` is a transaction, the
+    /// event goes out when it's committed, and not at all if it's rolled
+    /// back.
+    pub async fn publish<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+    ) -> Result<(), sqlx::Error> {
+        let payload = serde_json::to_string(self).expect("published events to be serializable");
+
+        // `pg_notify` returns `void`, which the query macros can't describe.
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(CHANNEL)
+            .bind(payload)
+            .execute(executor)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// A subsystem that caches something, and needs to know when it's outdated.
+/// This is called from the listener task, so anything slow should be handed
+/// off to the subsystem's own task.
+pub trait InvalidationSubscriber: Debug + Send + Sync {
+    fn invalidate(&self, event: &InvalidationEvent);
+}
+
+/// Everyone who gets [InvalidationEvent]s from [run_listener].
+#[derive(Debug, Default)]
+pub struct InvalidationRegistry {
+    subscribers: RwLock<Vec<Arc<dyn InvalidationSubscriber>>>,
+}
+
+impl InvalidationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, subscriber: Arc<dyn InvalidationSubscriber>) {
+        self.subscribers
+            .write()
+            .expect("the subscriber lock to not be poisoned")
+            .push(subscriber);
+    }
+
+    fn dispatch(&self, event: &InvalidationEvent) {
+        for subscriber in self
+            .subscribers
+            .read()
+            .expect("the subscriber lock to not be poisoned")
+            .iter()
+        {
+            subscriber.invalidate(event);
+        }
+    }
+}
+
+/// How the listener got on so far, across reconnects.
+#[derive(Debug, Default)]
+struct ListenerState {
+    has_connected: bool,
+    failures: u32,
+}
+
+/// Listens until something goes wrong that reconnecting doesn't fix.
+async fn listen(
+    app_state: &AppState,
+    pool: &PgPool,
+    state: &mut ListenerState,
+) -> Result<(), sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    info!("listening for invalidation events");
+
+    if state.has_connected {
+        app_state.invalidation.dispatch(&InvalidationEvent::Resync);
+    }
+    state.has_connected = true;
+    state.failures = 0;
+
+    loop {
+        // `try_recv` reconnects on its own, but tells us about it, unlike
+        // `recv`.
+        let Some(notification) = listener.try_recv().await? else {
+            warn!("invalidation listener lost its connection, resyncing");
+            app_state.invalidation.dispatch(&InvalidationEvent::Resync);
+            continue;
+        };
+
+        match serde_json::from_str(notification.payload()) {
+            Ok(event) => app_state.invalidation.dispatch(&event),
+            Err(err) => warn!(
+                "ignoring invalid invalidation event {:?}: {err}",
+                notification.payload()
+            ),
+        }
+    }
+}
+
+/// Dispatches [InvalidationEvent]s to the subscribers registered in the
+/// [AppState], forever. This runs as a background task in the web server.
+/// If the connection can't be re-established right away, this keeps trying
+/// with a backoff.
+pub async fn run_listener(app_state: AppState) {
+    // The listener holds on to its connection for good, so it gets a pool of
+    // its own instead of taking one away from the request handlers.
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .max_lifetime(None)
+        .idle_timeout(None)
+        .connect_lazy_with(app_state.database.connect_options().as_ref().clone());
+    let mut state = ListenerState::default();
+
+    loop {
+        if let Err(err) = listen(&app_state, &pool, &mut state).await {
+            error!("invalidation listener failed: {err:?}");
+        }
+
+        state.failures += 1;
+        let delay =
+            (RECONNECT_BASE_DELAY * 2_i32.pow(state.failures.min(7) - 1)).min(RECONNECT_MAX_DELAY);
+        tokio::time::sleep(delay.try_into().unwrap_or_default()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let event = InvalidationEvent::LevelHidden {
+            game: Game::Smm2,
+            level_id: "abc123def".to_owned(),
+            until: datetime!(2026-10-19 12:00 UTC),
+        };
+        let payload = serde_json::to_string(&event).unwrap();
+
+        assert_eq!(
+            payload,
+            r#"{"event":"level_hidden","game":"smm2","level_id":"abc123def","until":"2026-10-19T12:00:00Z"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<InvalidationEvent>(&payload).unwrap(),
+            event
+        );
+    }
+}
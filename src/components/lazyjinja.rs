@@ -71,6 +71,10 @@ impl LazyJinja {
         });
 
         base_env.add_filter("formatted_level_id", tpl_helpers::formatted_level_id);
+        base_env.add_filter(
+            "formatted_smm1_level_id",
+            tpl_helpers::formatted_smm1_level_id,
+        );
         base_env.add_filter("ms_to_minsecs", tpl_helpers::ms_to_minsecs);
         base_env.add_filter("tag_list", tpl_helpers::tag_list);
         base_env.add_filter("tag_name", tpl_helpers::tag_name);
@@ -0,0 +1,715 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex, RwLock},
+    time::Instant,
+};
+
+use rand::Rng;
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use tokio::sync::Notify;
+use tracing::{error, info};
+
+use crate::{
+    components::{
+        app_state::AppState,
+        invalidation::{InvalidationEvent, InvalidationSubscriber},
+    },
+    entities::{
+        level::{Game, Level},
+        smm2_level::{ClearConditionGroup, DataVersion, FilterParams, Smm2Level, TagMode},
+    },
+};
+
+/// A set of positions in [Snapshot::levels], one bit per level.
+#[derive(Clone, Debug, PartialEq)]
+struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    fn empty(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn full(len: usize) -> Self {
+        let mut bitset = Self::empty(len);
+        for position in 0..len {
+            bitset.insert(position);
+        }
+        bitset
+    }
+
+    fn insert(&mut self, position: usize) {
+        self.words[position / 64] |= 1 << (position % 64);
+    }
+
+    fn remove(&mut self, position: usize) {
+        self.words[position / 64] &= !(1 << (position % 64));
+    }
+
+    fn intersect_with(&mut self, other: &Bitset) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word &= other_word;
+        }
+    }
+
+    fn union_with(&mut self, other: &Bitset) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word |= other_word;
+        }
+    }
+
+    fn difference_with(&mut self, other: &Bitset) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word &= !other_word;
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// The position of the `n`th set bit, counting from zero.
+    fn nth(&self, mut n: usize) -> Option<usize> {
+        for (i, &word) in self.words.iter().enumerate() {
+            let ones = word.count_ones() as usize;
+            if n >= ones {
+                n -= ones;
+                continue;
+            }
+
+            let mut word = word;
+            for _ in 0..n {
+                word &= word - 1;
+            }
+            return Some(i * 64 + word.trailing_zeros() as usize);
+        }
+
+        None
+    }
+}
+
+/// A numeric column, sorted so ranges can be found with a binary search.
+#[derive(Debug)]
+struct SortedColumn {
+    entries: Vec<(i64, usize)>,
+}
+
+impl SortedColumn {
+    fn new(values: impl Iterator<Item = i64>) -> Self {
+        let mut entries: Vec<(i64, usize)> = values.zip(0..).collect();
+        entries.sort_unstable();
+        Self { entries }
+    }
+
+    /// All positions with `min <= value <= max`, where unset bounds are open.
+    fn range(&self, min: Option<i64>, max: Option<i64>, len: usize) -> Bitset {
+        let start = min.map_or(0, |min| self.entries.partition_point(|&(v, _)| v < min));
+        let end = max.map_or(self.entries.len(), |max| {
+            self.entries.partition_point(|&(v, _)| v <= max)
+        });
+
+        let mut bitset = Bitset::empty(len);
+        for &(_, position) in self.entries.get(start..end).unwrap_or_default() {
+            bitset.insert(position);
+        }
+        bitset
+    }
+}
+
+/// Everything the index knows at one point in time. Snapshots are never
+/// changed after they're built, a reload just replaces them.
+#[derive(Debug)]
+struct Snapshot {
+    version: DataVersion,
+    levels: Vec<Smm2Level>,
+    positions: HashMap<String, usize>,
+    pending_clears: Vec<(usize, OffsetDateTime)>,
+
+    by_year: HashMap<i64, Bitset>,
+    by_style: HashMap<String, Bitset>,
+    by_theme: HashMap<String, Bitset>,
+    by_tag: HashMap<String, Bitset>,
+    by_clear_condition: HashMap<Option<i64>, Bitset>,
+
+    attempts: SortedColumn,
+    footprints: SortedColumn,
+    clearcheck_ms: SortedColumn,
+}
+
+impl Snapshot {
+    fn build(
+        version: DataVersion,
+        levels: Vec<Smm2Level>,
+        pending_clears: Vec<(String, OffsetDateTime)>,
+    ) -> Self {
+        let len = levels.len();
+        let positions: HashMap<String, usize> = levels
+            .iter()
+            .enumerate()
+            .map(|(position, level)| (level.id.clone(), position))
+            .collect();
+
+        fn group<K: Eq + Hash>(
+            len: usize,
+            keys: impl Iterator<Item = (usize, K)>,
+        ) -> HashMap<K, Bitset> {
+            let mut groups: HashMap<K, Bitset> = HashMap::new();
+            for (position, key) in keys {
+                groups
+                    .entry(key)
+                    .or_insert_with(|| Bitset::empty(len))
+                    .insert(position);
+            }
+            groups
+        }
+
+        let indexed = || levels.iter().enumerate();
+        Self {
+            version,
+            pending_clears: pending_clears
+                .into_iter()
+                .filter_map(|(id, until)| Some((*positions.get(&id)?, until)))
+                .collect(),
+            positions,
+
+            by_year: group(len, indexed().map(|(p, l)| (p, l.year))),
+            by_style: group(len, indexed().map(|(p, l)| (p, l.style.to_lowercase()))),
+            by_theme: group(len, indexed().map(|(p, l)| (p, l.theme.to_lowercase()))),
+            by_tag: group(
+                len,
+                indexed().flat_map(|(p, l)| l.tags.iter().map(move |t| (p, t.to_lowercase()))),
+            ),
+            by_clear_condition: group(len, indexed().map(|(p, l)| (p, l.clear_condition))),
+
+            attempts: SortedColumn::new(levels.iter().map(|l| l.attempts)),
+            footprints: SortedColumn::new(levels.iter().map(|l| l.footprints)),
+            clearcheck_ms: SortedColumn::new(levels.iter().map(|l| l.clearcheck_ms)),
+
+            levels,
+        }
+    }
+
+    /// The same levels [Smm2Level::get_random_level] would pick from.
+    fn matches(
+        &self,
+        params: &FilterParams,
+        hidden: &HashMap<String, Hidden>,
+        now: OffsetDateTime,
+    ) -> Bitset {
+        let len = self.levels.len();
+        let empty = Bitset::empty(len);
+
+        let mut matches = Bitset::full(len);
+        if let Some(year) = params.year {
+            matches.intersect_with(self.by_year.get(&year).unwrap_or(&empty));
+        }
+        if !params.style.is_empty() {
+            matches.intersect_with(&union_of(
+                len,
+                &self.by_style,
+                params.style.iter().map(filter_key),
+            ));
+        }
+        matches.difference_with(&union_of(
+            len,
+            &self.by_style,
+            params.exclude_style.iter().map(filter_key),
+        ));
+        if !params.theme.is_empty() {
+            matches.intersect_with(&union_of(
+                len,
+                &self.by_theme,
+                params.theme.iter().map(filter_key),
+            ));
+        }
+        matches.difference_with(&union_of(
+            len,
+            &self.by_theme,
+            params.exclude_theme.iter().map(filter_key),
+        ));
+        match params.tag_mode.unwrap_or_default() {
+            TagMode::Any if !params.tag.is_empty() => {
+                matches.intersect_with(&union_of(
+                    len,
+                    &self.by_tag,
+                    params.tag.iter().map(filter_key),
+                ));
+            }
+            TagMode::Any => {}
+            TagMode::All => {
+                for tag in &params.tag {
+                    matches.intersect_with(self.by_tag.get(&filter_key(tag)).unwrap_or(&empty));
+                }
+            }
+        }
+        matches.difference_with(&union_of(
+            len,
+            &self.by_tag,
+            params.exclude_tag.iter().map(filter_key),
+        ));
+        if !params.clear_condition_group.is_empty() {
+            matches.intersect_with(&union_of(
+                len,
+                &self.by_clear_condition,
+                clear_condition_keys(&params.clear_condition_group),
+            ));
+        }
+        matches.difference_with(&union_of(
+            len,
+            &self.by_clear_condition,
+            clear_condition_keys(&params.exclude_clear_condition_group),
+        ));
+
+        let ranges = [
+            (&self.attempts, params.min_attempts, params.max_attempts),
+            (
+                &self.footprints,
+                params.min_footprints,
+                params.max_footprints,
+            ),
+            (
+                &self.clearcheck_ms,
+                params.min_clearcheck_ms,
+                params.max_clearcheck_ms,
+            ),
+        ];
+        for (column, min, max) in ranges {
+            if min.is_some() || max.is_some() {
+                matches.intersect_with(&column.range(min, max, len));
+            }
+        }
+
+        for &(position, until) in &self.pending_clears {
+            if until > now {
+                matches.remove(position);
+            }
+        }
+        for (id, hidden) in hidden {
+            if let Some(&position) = self.positions.get(id)
+                && hidden.is_hidden_at(now)
+            {
+                matches.remove(position);
+            }
+        }
+
+        matches
+    }
+}
+
+/// All levels in any of the groups with the given keys.
+fn union_of<K: Eq + Hash>(
+    len: usize,
+    groups: &HashMap<K, Bitset>,
+    keys: impl IntoIterator<Item = K>,
+) -> Bitset {
+    let mut union = Bitset::empty(len);
+    for key in keys {
+        if let Some(bitset) = groups.get(&key) {
+            union.union_with(bitset);
+        }
+    }
+    union
+}
+
+/// The keys of [Snapshot::by_clear_condition] for levels in any of the
+/// `groups`.
+fn clear_condition_keys(groups: &[ClearConditionGroup]) -> Vec<Option<i64>> {
+    groups
+        .iter()
+        .flat_map(|group| match group.id_list() {
+            None => vec![None],
+            Some(ids) => ids.into_iter().map(Some).collect(),
+        })
+        .collect()
+}
+
+/// The name a filter value has in the query string. For styles, themes, and
+/// tags, that's what the database stores as well, give or take the case.
+fn filter_key<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_lowercase))
+        .unwrap_or_default()
+}
+
+/// A level hidden after the current snapshot was loaded.
+#[derive(Clone, Copy, Debug)]
+struct Hidden {
+    until: OffsetDateTime,
+    recorded_at: Instant,
+}
+
+impl Hidden {
+    fn is_hidden_at(&self, now: OffsetDateTime) -> bool {
+        self.until > now
+    }
+}
+
+/// What [LevelIndex::pick] found.
+#[derive(Clone, Debug)]
+pub struct IndexedPick<L> {
+    pub level: Option<L>,
+    /// How many levels matched the filters.
+    pub matches: usize,
+}
+
+/// An in-memory copy of all SMM2 levels, with bitsets for the filters that
+/// compare by equality and sorted columns for the ranges. This answers random
+/// level queries without going to the database. Until the first load
+/// finished, or if the index is disabled, there are no answers, and callers
+/// need to ask the database instead.
+#[derive(Debug, Default)]
+pub struct LevelIndex {
+    snapshot: RwLock<Option<Arc<Snapshot>>>,
+
+    /// Levels hidden by clear reports since the snapshot was loaded. They're
+    /// in the database as well, and the next snapshot picks them up from
+    /// there.
+    hidden: Mutex<HashMap<String, Hidden>>,
+
+    /// Wakes up [run_refresher] for a reload.
+    reload_wanted: Notify,
+}
+
+impl LevelIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn snapshot(&self) -> Option<Arc<Snapshot>> {
+        self.snapshot
+            .read()
+            .expect("the snapshot lock to not be poisoned")
+            .clone()
+    }
+
+    fn matches(&self, params: &FilterParams) -> Option<(Arc<Snapshot>, Bitset)> {
+        let snapshot = self.snapshot()?;
+        let hidden = self
+            .hidden
+            .lock()
+            .expect("the hidden levels lock to not be poisoned");
+        let matches = snapshot.matches(params, &hidden, OffsetDateTime::now_utc());
+        drop(hidden);
+
+        Some((snapshot, matches))
+    }
+
+    /// Picks a random level matching the filters. Returns `None` if the index
+    /// isn't loaded.
+    pub fn pick(&self, params: &FilterParams) -> Option<IndexedPick<Smm2Level>> {
+        let (snapshot, matches) = self.matches(params)?;
+        let count = matches.count();
+        let level = (count > 0)
+            .then(|| matches.nth(rand::rng().random_range(0..count)))
+            .flatten()
+            .map(|position| snapshot.levels[position].clone());
+
+        Some(IndexedPick {
+            level,
+            matches: count,
+        })
+    }
+
+    /// Counts the levels matching the filters. Returns `None` if the index
+    /// isn't loaded.
+    pub fn count(&self, params: &FilterParams) -> Option<usize> {
+        Some(self.matches(params)?.1.count())
+    }
+
+    /// Mirrors [crate::entities::level::Level::hide_pending_clear], so the
+    /// index doesn't keep picking a level that was just reported as cleared.
+    pub fn hide(&self, game: Game, level_id: &str, until: OffsetDateTime) {
+        if game != Game::Smm2 {
+            return;
+        }
+
+        let mut hidden = self
+            .hidden
+            .lock()
+            .expect("the hidden levels lock to not be poisoned");
+        let entry = hidden.entry(level_id.to_owned()).or_insert(Hidden {
+            until,
+            recorded_at: Instant::now(),
+        });
+        entry.until = entry.until.max(until);
+        entry.recorded_at = Instant::now();
+    }
+
+    /// Loads a fresh snapshot from the database.
+    pub async fn reload(&self, db: &PgPool) -> Result<(), sqlx::Error> {
+        let started_at = Instant::now();
+        // The version goes first. If an import finishes while we're loading,
+        // that's one reload too many, but never one too few.
+        let version = Smm2Level::data_version(db).await?;
+        let levels = Smm2Level::get_all(db).await?;
+        let pending_clears = Smm2Level::get_pending_clears(db).await?;
+        let snapshot = Snapshot::build(version, levels, pending_clears);
+        let level_count = snapshot.levels.len();
+
+        *self
+            .snapshot
+            .write()
+            .expect("the snapshot lock to not be poisoned") = Some(Arc::new(snapshot));
+        self.hidden
+            .lock()
+            .expect("the hidden levels lock to not be poisoned")
+            .retain(|_, hidden| hidden.recorded_at >= started_at);
+
+        info!(
+            "loaded {level_count} levels into the level index in {:?}",
+            started_at.elapsed()
+        );
+        Ok(())
+    }
+
+    async fn reload_if_outdated(&self, db: &PgPool) -> Result<(), sqlx::Error> {
+        let loaded_version = self.snapshot().map(|s| s.version);
+        if loaded_version != Some(Smm2Level::data_version(db).await?) {
+            self.reload(db).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl InvalidationSubscriber for LevelIndex {
+    fn invalidate(&self, event: &InvalidationEvent) {
+        match event {
+            InvalidationEvent::LevelHidden {
+                game,
+                level_id,
+                until,
+            } => self.hide(*game, level_id, *until),
+            InvalidationEvent::LevelsImported { game: Game::Smm2 }
+            | InvalidationEvent::BlocklistChanged {
+                game: Game::Smm2, ..
+            }
+            | InvalidationEvent::LevelArchived {
+                game: Game::Smm2, ..
+            }
+            | InvalidationEvent::LevelRestored {
+                game: Game::Smm2, ..
+            }
+            | InvalidationEvent::Resync => self.reload_wanted.notify_one(),
+            _ => {}
+        }
+    }
+}
+
+/// Keeps the level index up to date, forever. This runs as a background task
+/// in the web server. The index is reloaded right away when an
+/// [InvalidationEvent] asks for it, and otherwise checked every once in a
+/// while, in case an event got lost.
+pub async fn run_refresher(app_state: AppState) {
+    let refresh_interval =
+        Duration::seconds(app_state.settings.level_index_refresh_secs.max(1).into());
+    info!("starting level index refresher");
+
+    let mut reload_wanted = false;
+    loop {
+        let index = &app_state.level_index;
+        let result = if reload_wanted {
+            index.reload(&app_state.database).await
+        } else {
+            index.reload_if_outdated(&app_state.database).await
+        };
+        if let Err(err) = result {
+            error!("refreshing the level index failed: {err:?}");
+        }
+
+        reload_wanted = tokio::select! {
+            () = index.reload_wanted.notified() => true,
+            () = tokio::time::sleep(refresh_interval.try_into().unwrap_or_default()) => false,
+        };
+    }
+}
+
+/// Picks a random level from the in-memory index if there is one, and from
+/// the database otherwise. The number of matching levels is only known if
+/// the index answered, since counting in the database isn't free.
+pub async fn pick_random_level<L: Level>(
+    app_state: &AppState,
+    params: &L::Filter,
+) -> Result<(Option<L>, Option<usize>), sqlx::Error> {
+    match L::pick_from_index(&app_state.level_index, params) {
+        Some(pick) => Ok((pick.level, Some(pick.matches))),
+        None => Ok((
+            L::get_random_level(&app_state.database, params).await?,
+            None,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::entities::smm2_level::{Style, Tag};
+
+    fn level(id: &str, attempts: i64, style: &str, tags: &[&str]) -> Smm2Level {
+        Smm2Level {
+            id: id.to_owned(),
+            year: 2024,
+            title: "Test Level".to_owned(),
+            description: None,
+            uploaded_at: datetime!(2024-06-15 14:31:13 UTC),
+            clearcheck_ms: 19233,
+            attempts,
+            footprints: 5,
+            likes: 0,
+            boos: 0,
+            comments: 0,
+            clear_condition: None,
+            clear_condition_magnitude: None,
+            style: style.to_owned(),
+            theme: "sky".to_owned(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn matching_ids<'a>(snapshot: &'a Snapshot, params: &FilterParams) -> Vec<&'a str> {
+        let matches = snapshot.matches(params, &HashMap::new(), OffsetDateTime::now_utc());
+        (0..matches.count())
+            .map(|n| snapshot.levels[matches.nth(n).unwrap()].id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn bitset_finds_the_nth_set_bit() {
+        let mut bitset = Bitset::empty(200);
+        for position in [3, 64, 65, 130] {
+            bitset.insert(position);
+        }
+        bitset.remove(65);
+
+        assert_eq!(bitset.count(), 3);
+        assert_eq!(bitset.nth(0), Some(3));
+        assert_eq!(bitset.nth(1), Some(64));
+        assert_eq!(bitset.nth(2), Some(130));
+        assert_eq!(bitset.nth(3), None);
+    }
+
+    #[test]
+    fn snapshot_matches_like_the_database() {
+        let snapshot = Snapshot::build(
+            DataVersion {
+                last_import_run_id: None,
+                last_archive_id: None,
+            },
+            vec![
+                level("aaa", 10, "SMW", &["speedrun"]),
+                level("bbb", 20, "SMW", &["themed", "speedrun"]),
+                level("ccc", 30, "SM3DW", &[]),
+                level("ddd", 40, "SMW", &[]),
+            ],
+            vec![("ddd".to_owned(), datetime!(2999-01-01 00:00 UTC))],
+        );
+
+        assert_eq!(
+            matching_ids(&snapshot, &FilterParams::default()),
+            vec!["aaa", "bbb", "ccc"]
+        );
+        assert_eq!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    style: vec![Style::SMW],
+                    tag: vec![Tag::Speedrun],
+                    min_attempts: Some(15),
+                    ..FilterParams::default()
+                }
+            ),
+            vec!["bbb"]
+        );
+        assert_eq!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    max_attempts: Some(20),
+                    clear_condition_group: vec![ClearConditionGroup::None],
+                    ..FilterParams::default()
+                }
+            ),
+            vec!["aaa", "bbb"]
+        );
+        assert!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    year: Some(2019),
+                    ..FilterParams::default()
+                }
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn snapshot_matches_lists_and_exclusions() {
+        let snapshot = Snapshot::build(
+            DataVersion {
+                last_import_run_id: None,
+                last_archive_id: None,
+            },
+            vec![
+                level("aaa", 10, "SMW", &["speedrun"]),
+                level("bbb", 20, "SMB3", &["themed", "speedrun"]),
+                level("ccc", 30, "SM3DW", &["music"]),
+                level("ddd", 40, "SMW", &[]),
+            ],
+            vec![],
+        );
+
+        assert_eq!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    style: vec![Style::SMW, Style::SMB3],
+                    exclude_tag: vec![Tag::Themed],
+                    ..FilterParams::default()
+                }
+            ),
+            vec!["aaa", "ddd"]
+        );
+        assert_eq!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    tag: vec![Tag::Speedrun, Tag::Music],
+                    ..FilterParams::default()
+                }
+            ),
+            vec!["aaa", "bbb", "ccc"]
+        );
+        assert_eq!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    tag: vec![Tag::Speedrun, Tag::Themed],
+                    tag_mode: Some(TagMode::All),
+                    ..FilterParams::default()
+                }
+            ),
+            vec!["bbb"]
+        );
+        assert_eq!(
+            matching_ids(
+                &snapshot,
+                &FilterParams {
+                    exclude_style: vec![Style::SMW],
+                    exclude_clear_condition_group: vec![ClearConditionGroup::NoJumping],
+                    ..FilterParams::default()
+                }
+            ),
+            vec!["bbb", "ccc"]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use sqlx::PgPool;
+
+use crate::{
+    components::invalidation::InvalidationEvent,
+    entities::{
+        level::{Game, Level},
+        level_archive::RemovalReason,
+        smm1_level::Smm1Level,
+        smm2_level::Smm2Level,
+    },
+};
+
+/// Everything that can go wrong when removing a level by hand.
+#[derive(Debug, thiserror::Error)]
+pub enum LevelRemovalError {
+    #[error("`{0}` is not a valid level ID")]
+    InvalidLevelId(String),
+
+    #[error(transparent)]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Removes a level from the randomizer and archives it as
+/// [RemovalReason::ManuallyRemoved]. This is meant for levels that are known
+/// to be cleared before upstream knows about it, so the importer won't bring
+/// them back for a while. Broken levels belong on the blocklist instead. Returns `false`
+/// if there was no such level.
+pub async fn remove(
+    db: &PgPool,
+    game: Game,
+    raw_level_id: &str,
+) -> Result<bool, LevelRemovalError> {
+    match game {
+        Game::Smm1 => remove_level::<Smm1Level>(db, raw_level_id).await,
+        Game::Smm2 => remove_level::<Smm2Level>(db, raw_level_id).await,
+    }
+}
+
+async fn remove_level<L: Level>(
+    db: &PgPool,
+    raw_level_id: &str,
+) -> Result<bool, LevelRemovalError> {
+    let level_id = L::normalized_internal_level_id(raw_level_id);
+    if !L::is_valid_level_id(&level_id) {
+        return Err(LevelRemovalError::InvalidLevelId(raw_level_id.to_owned()));
+    }
+
+    let mut db_transaction = db.begin().await?;
+    let archived = L::archive_many(
+        &mut db_transaction,
+        std::slice::from_ref(&level_id),
+        RemovalReason::ManuallyRemoved,
+    )
+    .await?;
+    if archived.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    InvalidationEvent::LevelArchived {
+        game: L::GAME,
+        level_id,
+    }
+    .publish(&mut *db_transaction)
+    .await?;
+    db_transaction.commit().await?;
+
+    Ok(true)
+}
+
+/// Undoes [remove], or an archival because a clear was verified, by moving
+/// the level back from the archive into the randomizer. Returns `false` if
+/// the level's most recent archive entry isn't one of those, or if the level
+/// is in the randomizer already.
+pub async fn restore(
+    db: &PgPool,
+    game: Game,
+    raw_level_id: &str,
+) -> Result<bool, LevelRemovalError> {
+    match game {
+        Game::Smm1 => restore_level::<Smm1Level>(db, raw_level_id).await,
+        Game::Smm2 => restore_level::<Smm2Level>(db, raw_level_id).await,
+    }
+}
+
+async fn restore_level<L: Level>(
+    db: &PgPool,
+    raw_level_id: &str,
+) -> Result<bool, LevelRemovalError> {
+    let level_id = L::normalized_internal_level_id(raw_level_id);
+    if !L::is_valid_level_id(&level_id) {
+        return Err(LevelRemovalError::InvalidLevelId(raw_level_id.to_owned()));
+    }
+
+    let mut db_transaction = db.begin().await?;
+    let restored = L::restore_archived(&mut db_transaction, &level_id).await?;
+    if restored.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    InvalidationEvent::LevelRestored {
+        game: L::GAME,
+        level_id,
+    }
+    .publish(&mut *db_transaction)
+    .await?;
+    db_transaction.commit().await?;
+
+    Ok(true)
+}
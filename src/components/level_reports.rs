@@ -0,0 +1,112 @@
+use sqlx::PgPool;
+
+use crate::{
+    components::blocklist::{self, BlocklistError},
+    entities::{
+        level::{Game, Level},
+        level_report::{LevelReport, ReportCategory, ReportStatus, ReportedLevel},
+        smm1_level::Smm1Level,
+        smm2_level::Smm2Level,
+    },
+};
+
+/// Notes longer than this are rejected. Nobody needs a novel to explain why a
+/// level is broken.
+pub const MAX_NOTE_LENGTH: usize = 1000;
+
+/// Everything that can go wrong when reporting levels or moderating reports.
+#[derive(Debug, thiserror::Error)]
+pub enum LevelReportError {
+    #[error("`{0}` is not a valid level ID")]
+    InvalidLevelId(String),
+
+    #[error("level `{0}` does not exist")]
+    UnknownLevel(String),
+
+    #[error("the note can't be longer than {MAX_NOTE_LENGTH} characters")]
+    NoteTooLong,
+
+    #[error("there are no open reports for level `{0}`")]
+    NoOpenReports(String),
+
+    #[error(transparent)]
+    BlocklistError(#[from] BlocklistError),
+
+    #[error(transparent)]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Stores a player's report about a broken level. Only levels that are
+/// currently in the randomizer can be reported.
+pub async fn report(
+    db: &PgPool,
+    game: Game,
+    raw_level_id: &str,
+    category: ReportCategory,
+    note: Option<&str>,
+) -> Result<LevelReport, LevelReportError> {
+    match game {
+        Game::Smm1 => report_level::<Smm1Level>(db, raw_level_id, category, note).await,
+        Game::Smm2 => report_level::<Smm2Level>(db, raw_level_id, category, note).await,
+    }
+}
+
+async fn report_level<L: Level>(
+    db: &PgPool,
+    raw_level_id: &str,
+    category: ReportCategory,
+    note: Option<&str>,
+) -> Result<LevelReport, LevelReportError> {
+    let level_id = L::normalized_internal_level_id(raw_level_id);
+    if !L::is_valid_level_id(&level_id) {
+        return Err(LevelReportError::InvalidLevelId(raw_level_id.to_owned()));
+    }
+
+    let note = note.map(str::trim).filter(|n| !n.is_empty());
+    if note.is_some_and(|n| n.chars().count() > MAX_NOTE_LENGTH) {
+        return Err(LevelReportError::NoteTooLong);
+    }
+
+    if !L::id_exists(db, &level_id).await {
+        return Err(LevelReportError::UnknownLevel(level_id));
+    }
+
+    Ok(LevelReport::store(db, L::GAME, &level_id, category, note).await?)
+}
+
+/// Approves all open reports for a level, which puts the level on the
+/// blocklist. Both happen in the same transaction, so a level can't end up
+/// blocklisted with its reports still open.
+pub async fn approve(db: &PgPool, game: Game, level_id: &str) -> Result<u64, LevelReportError> {
+    let mut db_transaction = db.begin().await?;
+    let Some(reported_level) =
+        ReportedLevel::get_open(&mut *db_transaction, Some(game), Some(level_id))
+            .await?
+            .into_iter()
+            .next()
+    else {
+        return Err(LevelReportError::NoOpenReports(level_id.to_owned()));
+    };
+
+    blocklist::add_in_transaction(
+        &mut db_transaction,
+        game,
+        level_id,
+        Some(&reported_level.blocklist_reason()),
+    )
+    .await?;
+    let resolved =
+        LevelReport::resolve_all(&mut *db_transaction, game, level_id, ReportStatus::Approved)
+            .await?;
+    db_transaction.commit().await?;
+
+    Ok(resolved)
+}
+
+/// Rejects all open reports for a level. The level stays in the randomizer.
+pub async fn reject(db: &PgPool, game: Game, level_id: &str) -> Result<u64, LevelReportError> {
+    match LevelReport::resolve_all(db, game, level_id, ReportStatus::Rejected).await? {
+        0 => Err(LevelReportError::NoOpenReports(level_id.to_owned())),
+        resolved => Ok(resolved),
+    }
+}
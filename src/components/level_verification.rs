@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use reqwest::Url;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{
+    components::settings::Settings,
+    entities::{
+        clear_report::{Verification, VerificationStatus},
+        level::Game,
+    },
+};
+
+/// Reports are accepted unverified if the API takes longer than this, so a
+/// slow API doesn't hold up the user.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The interesting bits of a `level_info` response.
+#[derive(Debug, Deserialize)]
+struct LevelInfo {
+    clears: Option<i64>,
+}
+
+/// Checks clear reports against a TheGreatRambler-style level-data API, which
+/// answers `GET {base_url}/level_info/{level_id}` with the level's stats.
+/// Only SMM2 levels can be checked.
+#[derive(Clone, Debug)]
+pub struct LevelVerifier {
+    client: reqwest::Client,
+    base_url: Option<Url>,
+}
+
+impl LevelVerifier {
+    /// Without a `base_url`, verification is disabled and [Self::verify]
+    /// always returns `None`.
+    pub fn new(base_url: Option<Url>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("the http client to build"),
+            base_url,
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(settings.level_verification_base_url.clone())
+    }
+
+    /// Asks the API whether anyone cleared the level. Returns `None` if
+    /// verification is disabled or not supported for the game. Failures
+    /// count as [VerificationStatus::Unverified], they never reject a report.
+    #[tracing::instrument(skip(self))]
+    pub async fn verify(&self, game: Game, level_id: &str) -> Option<Verification> {
+        let base_url = self.base_url.as_ref()?;
+        if game != Game::Smm2 {
+            return None;
+        }
+
+        let upstream_clears = match self.fetch_clears(base_url, game, level_id).await {
+            Ok(clears) => clears,
+            Err(err) => {
+                warn!("verifying clear failed: {err}");
+                None
+            }
+        };
+
+        let status = match upstream_clears {
+            Some(0) => VerificationStatus::Contradicted,
+            Some(_) => VerificationStatus::Verified,
+            None => VerificationStatus::Unverified,
+        };
+
+        Some(Verification {
+            status,
+            upstream_clears,
+        })
+    }
+
+    /// Returns `None` if the API doesn't know the level.
+    async fn fetch_clears(
+        &self,
+        base_url: &Url,
+        game: Game,
+        level_id: &str,
+    ) -> Result<Option<i64>, reqwest::Error> {
+        let response = self
+            .client
+            .get(level_info_url(base_url, &game.formatted_level_id(level_id)))
+            .send()
+            .await?;
+
+        // TGR answers unknown levels with a 400 and an error message.
+        if response.status().is_client_error() {
+            return Ok(None);
+        }
+
+        Ok(response
+            .error_for_status()?
+            .json::<LevelInfo>()
+            .await?
+            .clears)
+    }
+}
+
+fn level_info_url(base_url: &Url, formatted_level_id: &str) -> Url {
+    let mut url = base_url.clone();
+    url.path_segments_mut()
+        .expect("the level verification url to be a base url")
+        .pop_if_empty()
+        .extend(["level_info", formatted_level_id]);
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_info_url_appends_to_the_base_path() {
+        let base_url = Url::parse("https://tgrcode.com/mm2/").unwrap();
+        assert_eq!(
+            level_info_url(&base_url, "ABC-DEF-GHJ").as_str(),
+            "https://tgrcode.com/mm2/level_info/ABC-DEF-GHJ"
+        );
+    }
+}
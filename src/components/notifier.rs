@@ -0,0 +1,110 @@
+use std::future::Future;
+
+use reqwest::{StatusCode, header::RETRY_AFTER};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::{Duration, OffsetDateTime};
+
+use crate::entities::{
+    clear_report::{ClearReport, ReportChannel},
+    level::Game,
+};
+
+pub mod discord;
+pub mod json_webhook;
+pub mod log;
+
+/// Specifies where clear reports get delivered to
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum NotifierKind {
+    /// Posts `!clear` messages to a Discord webhook
+    Discord,
+    /// Posts the clear report as JSON to an arbitrary URL
+    JsonWebhook,
+    /// Only writes clear reports to the log, for local development
+    Log,
+}
+
+/// How long to wait after a `429` if the other side didn't say.
+const DEFAULT_RETRY_AFTER: Duration = Duration::seconds(5);
+
+#[derive(Debug, Error)]
+pub enum NotifierError {
+    #[error("rate limited, retry after {0}")]
+    RateLimited(Duration),
+
+    #[error("unexpected response status `{0}`")]
+    UnexpectedStatus(StatusCode),
+
+    #[error("request failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+}
+
+/// The interesting part of Discord's `429` response body.
+#[derive(Debug, Deserialize)]
+struct RateLimitResponse {
+    retry_after: f64,
+}
+
+/// Figures out how long a rate-limited webhook wants us to wait. Discord's
+/// `retry_after` in the body is preferred over the `Retry-After` header, since
+/// that one has sub-second precision.
+pub(crate) async fn retry_after(response: reqwest::Response) -> Duration {
+    let header_value = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<f64>().ok());
+
+    let body_value = response
+        .json::<RateLimitResponse>()
+        .await
+        .ok()
+        .map(|r| r.retry_after);
+
+    body_value
+        .or(header_value)
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::seconds_f64)
+        .unwrap_or(DEFAULT_RETRY_AFTER)
+}
+
+/// Everything a [Notifier] gets to know about a clear report.
+#[derive(Clone, Debug, Serialize)]
+pub struct ClearNotification {
+    pub report_id: i64,
+    pub game: Game,
+    pub level_id: String,
+    /// The level ID the way the game shows it, like `ABC-DEF-GHJ`.
+    pub formatted_level_id: String,
+    pub channel: ReportChannel,
+    pub source: Option<String>,
+    /// The URL of the tool the report came from, if it's a known source.
+    pub source_url: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub reported_at: OffsetDateTime,
+}
+
+impl ClearNotification {
+    pub fn new(report: &ClearReport, source_url: Option<String>) -> Self {
+        Self {
+            report_id: report.id,
+            game: report.game,
+            level_id: report.level_id.clone(),
+            formatted_level_id: report.game.formatted_level_id(&report.level_id),
+            channel: report.channel,
+            source: report.source.clone(),
+            source_url,
+            reported_at: report.created_at,
+        }
+    }
+}
+
+/// Something clear reports can be delivered to. The delivery worker takes
+/// care of retries, so implementations should just try once and report back.
+pub trait Notifier: Send + Sync + 'static {
+    fn notify_clear(
+        &self,
+        notification: &ClearNotification,
+    ) -> impl Future<Output = Result<(), NotifierError>> + Send;
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use reqwest::{StatusCode, Url};
+use tracing::error;
+
+use crate::components::notifier::{ClearNotification, Notifier, NotifierError, retry_after};
+
+/// Posts `!clear ABC-DEF-GHJ` messages to a Discord webhook, which is where
+/// the team's clear-checking bot picks them up.
+#[derive(Clone, Debug)]
+pub struct DiscordNotifier {
+    client: reqwest::Client,
+    webhook_url: Url,
+}
+
+impl DiscordNotifier {
+    /// `api_base_url` is Discord's API root, like `https://discord.com/api`.
+    /// Pointing it somewhere else is mostly useful for testing.
+    pub fn new(api_base_url: &Url, webhook_id: &str, webhook_token: &str) -> Self {
+        let mut webhook_url = api_base_url.clone();
+        webhook_url
+            .path_segments_mut()
+            .expect("the discord api url to be a base url")
+            .pop_if_empty()
+            .extend(["webhooks", webhook_id, webhook_token]);
+
+        Self {
+            client: reqwest::Client::new(),
+            webhook_url,
+        }
+    }
+}
+
+impl Notifier for DiscordNotifier {
+    async fn notify_clear(&self, notification: &ClearNotification) -> Result<(), NotifierError> {
+        let (username, appendix) = match &notification.source_url {
+            Some(url) => ("api.smm-uncleared.com", format!(" ({url})")),
+            None => ("smm-uncleared.com", String::new()),
+        };
+
+        let mut body = HashMap::new();
+        body.insert("username", username);
+
+        let message = format!("!clear {}{appendix}", notification.formatted_level_id);
+        body.insert("content", &message);
+
+        let discord_response = self
+            .client
+            .post(self.webhook_url.clone())
+            .json(&body)
+            .send()
+            .await?;
+
+        match discord_response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::TOO_MANY_REQUESTS => Err(NotifierError::RateLimited(
+                retry_after(discord_response).await,
+            )),
+            status => {
+                error!("got status `{status}`");
+                Err(NotifierError::UnexpectedStatus(status))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_url_is_built_from_the_base_url() {
+        let notifier =
+            DiscordNotifier::new(&"https://discord.com/api/".parse().unwrap(), "1234", "abcd");
+        assert_eq!(
+            notifier.webhook_url.as_str(),
+            "https://discord.com/api/webhooks/1234/abcd"
+        );
+
+        let notifier =
+            DiscordNotifier::new(&"http://127.0.0.1:9000".parse().unwrap(), "1234", "abcd");
+        assert_eq!(
+            notifier.webhook_url.as_str(),
+            "http://127.0.0.1:9000/webhooks/1234/abcd"
+        );
+    }
+}
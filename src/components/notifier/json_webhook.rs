@@ -0,0 +1,39 @@
+use reqwest::{StatusCode, Url};
+
+use crate::components::notifier::{ClearNotification, Notifier, NotifierError, retry_after};
+
+/// Posts every [ClearNotification] as a JSON body to a fixed URL. Any `2xx`
+/// counts as delivered, and `429`s are honored the same way Discord's are.
+#[derive(Clone, Debug)]
+pub struct JsonWebhookNotifier {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl JsonWebhookNotifier {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+}
+
+impl Notifier for JsonWebhookNotifier {
+    async fn notify_clear(&self, notification: &ClearNotification) -> Result<(), NotifierError> {
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(notification)
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::TOO_MANY_REQUESTS => {
+                Err(NotifierError::RateLimited(retry_after(response).await))
+            }
+            status => Err(NotifierError::UnexpectedStatus(status)),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use tracing::info;
+
+use crate::components::notifier::{ClearNotification, Notifier, NotifierError};
+
+/// Doesn't deliver anything anywhere, just logs the clear reports. Good enough
+/// for local development.
+#[derive(Clone, Debug, Default)]
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    async fn notify_clear(&self, notification: &ClearNotification) -> Result<(), NotifierError> {
+        info!(
+            "clear report {} for {} level {} (source: {:?})",
+            notification.report_id,
+            notification.game,
+            notification.formatted_level_id,
+            notification.source
+        );
+        Ok(())
+    }
+}
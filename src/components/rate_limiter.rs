@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use axum::http::{HeaderMap, HeaderValue, header::RETRY_AFTER};
+
+use crate::components::settings::Settings;
+
+/// How many checks happen between two sweeps for buckets that have refilled
+/// completely, and can be forgotten.
+const PRUNE_INTERVAL: u64 = 1024;
+
+/// How long a token is remembered as belonging to an API source. This only
+/// decides which bucket a request is counted against, the handler still
+/// checks the token properly, so a rotated token or a disabled source doesn't
+/// get anywhere in the meantime.
+const API_SOURCE_TOKEN_TTL: Duration = Duration::from_secs(300);
+
+/// The groups of routes that are limited separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitClass {
+    /// Marking levels as cleared, and reporting broken levels. Both of these
+    /// end up in front of humans.
+    Reports,
+    /// Picking random levels, which is the most expensive query we have.
+    RandomLevels,
+}
+
+/// Who a request is counted against.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    Ip(IpAddr),
+    /// An authenticated API source, by its key. Sources get their own limits,
+    /// since they usually report for a lot of people at once.
+    ApiSource(String),
+    /// The client address isn't known, which should only happen in tests.
+    Unknown,
+}
+
+/// The size of a token bucket. A bucket holds up to `burst` requests, and
+/// refills at `per_minute`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BucketConfig {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl BucketConfig {
+    fn refill_per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// Where a client stands after a request. This ends up in the `RateLimit-*`
+/// response headers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset_after: Duration,
+    /// Time until the next request would be allowed, if this one wasn't.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitStatus {
+    /// Adds the `RateLimit-Limit`, `RateLimit-Remaining`, and `RateLimit-Reset`
+    /// headers, plus `Retry-After` if the request was limited. All times are
+    /// in seconds, rounded up.
+    pub fn insert_headers(&self, headers: &mut HeaderMap) {
+        let seconds = |d: Duration| HeaderValue::from(d.as_secs_f64().ceil() as u64);
+
+        headers.insert("ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("ratelimit-reset", seconds(self.reset_after));
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(
+                RETRY_AFTER,
+                seconds(retry_after.max(Duration::from_secs(1))),
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// In-memory token bucket rate limiter. Limits are per server process, which
+/// is good enough to stop someone from scripting the buttons.
+#[derive(Debug)]
+pub struct RateLimiter {
+    reports: BucketConfig,
+    random_levels: BucketConfig,
+    api_sources: BucketConfig,
+    buckets: Mutex<HashMap<(RateLimitClass, RateLimitKey), Bucket>>,
+    checks: AtomicU64,
+    /// API source keys by token hash, for tokens that recently turned out to
+    /// belong to an enabled source. Only valid tokens end up here, so this
+    /// can't grow past the number of sources.
+    api_source_tokens: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl RateLimiter {
+    pub fn new(
+        reports: BucketConfig,
+        random_levels: BucketConfig,
+        api_sources: BucketConfig,
+    ) -> Self {
+        Self {
+            reports,
+            random_levels,
+            api_sources,
+            buckets: Mutex::new(HashMap::new()),
+            checks: AtomicU64::new(0),
+            api_source_tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(
+            BucketConfig {
+                burst: settings.rate_limit_reports_burst,
+                per_minute: settings.rate_limit_reports_per_minute,
+            },
+            BucketConfig {
+                burst: settings.rate_limit_random_burst,
+                per_minute: settings.rate_limit_random_per_minute,
+            },
+            BucketConfig {
+                burst: settings.rate_limit_api_source_burst,
+                per_minute: settings.rate_limit_api_source_per_minute,
+            },
+        )
+    }
+
+    /// API sources share one set of limits for everything, but their buckets
+    /// are still kept separate per class.
+    fn config(&self, class: RateLimitClass, key: &RateLimitKey) -> BucketConfig {
+        match (class, key) {
+            (_, RateLimitKey::ApiSource(_)) => self.api_sources,
+            (RateLimitClass::Reports, _) => self.reports,
+            (RateLimitClass::RandomLevels, _) => self.random_levels,
+        }
+    }
+
+    /// Takes a token from the client's bucket. Returns `Err` if the bucket is
+    /// empty, and `Ok(None)` if this class isn't limited at all.
+    pub fn check(
+        &self,
+        class: RateLimitClass,
+        key: RateLimitKey,
+    ) -> Result<Option<RateLimitStatus>, RateLimitStatus> {
+        self.check_at(class, key, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        class: RateLimitClass,
+        key: RateLimitKey,
+        now: Instant,
+    ) -> Result<Option<RateLimitStatus>, RateLimitStatus> {
+        let config = self.config(class, &key);
+        if config.per_minute == 0 || config.burst == 0 {
+            return Ok(None);
+        }
+
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("rate limiter lock to not be poisoned");
+        if self
+            .checks
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(PRUNE_INTERVAL)
+        {
+            self.prune(&mut buckets, now);
+        }
+
+        let bucket = buckets.entry((class, key)).or_insert(Bucket {
+            tokens: config.burst as f64,
+            updated_at: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * config.refill_per_second())
+            .min(config.burst as f64);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        let seconds_until = |tokens: f64| {
+            Duration::from_secs_f64((tokens.max(0.0) / config.refill_per_second()).max(0.0))
+        };
+        let status = RateLimitStatus {
+            limit: config.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset_after: seconds_until(config.burst as f64 - bucket.tokens),
+            retry_after: (!allowed).then(|| seconds_until(1.0 - bucket.tokens)),
+        };
+
+        if allowed {
+            Ok(Some(status))
+        } else {
+            Err(status)
+        }
+    }
+
+    /// The key of the API source a token hash was recently seen with, if any.
+    pub fn api_source_for_token(&self, token_hash: &str) -> Option<String> {
+        self.api_source_for_token_at(token_hash, Instant::now())
+    }
+
+    fn api_source_for_token_at(&self, token_hash: &str, now: Instant) -> Option<String> {
+        self.api_source_tokens
+            .lock()
+            .expect("rate limiter lock to not be poisoned")
+            .get(token_hash)
+            .filter(|(_, seen_at)| now.saturating_duration_since(*seen_at) < API_SOURCE_TOKEN_TTL)
+            .map(|(source_key, _)| source_key.clone())
+    }
+
+    /// Remembers that a token belongs to an enabled API source, so the next
+    /// requests with it can be counted against the source without a lookup.
+    pub fn remember_api_source_token(&self, token_hash: String, source_key: String) {
+        self.remember_api_source_token_at(token_hash, source_key, Instant::now());
+    }
+
+    fn remember_api_source_token_at(&self, token_hash: String, source_key: String, now: Instant) {
+        self.api_source_tokens
+            .lock()
+            .expect("rate limiter lock to not be poisoned")
+            .insert(token_hash, (source_key, now));
+    }
+
+    /// Forgets all buckets that would be full by now anyway.
+    fn prune(&self, buckets: &mut HashMap<(RateLimitClass, RateLimitKey), Bucket>, now: Instant) {
+        buckets.retain(|(class, key), bucket| {
+            let config = self.config(*class, key);
+            let elapsed = now.saturating_duration_since(bucket.updated_at);
+            bucket.tokens + elapsed.as_secs_f64() * config.refill_per_second() < config.burst as f64
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_bursts_and_refills() {
+        let config = BucketConfig {
+            burst: 2,
+            per_minute: 60,
+        };
+        let limiter = RateLimiter::new(config, config, config);
+        let key = RateLimitKey::Ip("127.0.0.1".parse().unwrap());
+        let start = Instant::now();
+
+        let check = |offset: f64| {
+            limiter.check_at(
+                RateLimitClass::Reports,
+                key.clone(),
+                start + Duration::from_secs_f64(offset),
+            )
+        };
+
+        assert_eq!(check(0.0).unwrap().unwrap().remaining, 1);
+        assert_eq!(check(0.0).unwrap().unwrap().remaining, 0);
+
+        let limited = check(0.5).unwrap_err();
+        assert_eq!(limited.retry_after, Some(Duration::from_secs_f64(0.5)));
+
+        assert_eq!(check(1.0).unwrap().unwrap().remaining, 0);
+        assert!(
+            limiter
+                .check_at(RateLimitClass::RandomLevels, key.clone(), start)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn api_source_tokens_expire() {
+        let config = BucketConfig {
+            burst: 2,
+            per_minute: 60,
+        };
+        let limiter = RateLimiter::new(config, config, config);
+        let start = Instant::now();
+
+        assert_eq!(limiter.api_source_for_token_at("hash", start), None);
+        limiter.remember_api_source_token_at("hash".to_owned(), "tool".to_owned(), start);
+        assert_eq!(
+            limiter.api_source_for_token_at("hash", start + Duration::from_secs(60)),
+            Some("tool".to_owned())
+        );
+        assert_eq!(
+            limiter.api_source_for_token_at("hash", start + API_SOURCE_TOKEN_TTL),
+            None
+        );
+    }
+}
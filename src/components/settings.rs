@@ -1,7 +1,9 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
 
 use sqlx::postgres::PgConnectOptions;
 
+use crate::components::{import_source::ImportSourceKind, notifier::NotifierKind};
+
 /// Specifies the log's output format
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum LogFormat {
@@ -37,18 +39,111 @@ impl LogLevel {
 #[derive(Clone, Debug, clap::Parser)]
 #[clap(about, version, propagate_version = true)]
 pub struct Settings {
+    /// Admin API: bearer token required for all `/api/admin/` routes. If this
+    /// is not set, the admin API is disabled.
+    #[clap(long, env = "ADMIN_API_TOKEN")]
+    pub admin_api_token: Option<String>,
+
+    /// API: reject clear reports from clients that don't authenticate as an
+    /// API source. Without this, anonymous reports are allowed, as long as
+    /// they don't claim to come from a source.
+    #[clap(long, env = "API_REQUIRE_SOURCE_TOKEN")]
+    pub api_require_source_token: bool,
+
+    /// Clear reports: reports for a level that was already reported within
+    /// this many seconds are only recorded as confirmations, not delivered
+    /// again. `0` disables this.
+    #[clap(long, env = "CLEAR_REPORT_DEDUP_WINDOW_SECS", default_value_t = 600)]
+    pub clear_report_dedup_window_secs: u32,
+
+    /// Clear reports: for how many seconds a level reported as cleared is
+    /// hidden from the randomizer. The next import brings it back early if
+    /// the level is still uncleared upstream. `0` disables this.
+    #[clap(long, env = "CLEAR_REPORT_GRACE_PERIOD_SECS", default_value_t = 86400)]
+    pub clear_report_grace_period_secs: u32,
+
+    /// Clear reports: how often delivering a report to Discord is attempted
+    /// before it is dead-lettered. Rate-limited attempts don't count.
+    #[clap(long, env = "CLEAR_REPORT_MAX_ATTEMPTS", default_value_t = 10)]
+    pub clear_report_max_attempts: i32,
+
     /// The database URL to connect to. Needs to be a valid PostgreSQL
     /// connection URL, like `postgres://postgres@127.0.0.1/smm_zerop`
     #[clap(long, env = "DATABASE_URL")]
     pub database_url: PgConnectOptions,
 
+    /// Discord Webhook Bot: base URL of Discord's API. Only worth changing to
+    /// test against a local stand-in.
+    #[clap(
+        long,
+        env = "DISCORD_API_BASE_URL",
+        default_value = "https://discord.com/api"
+    )]
+    pub discord_api_base_url: reqwest::Url,
+
+    /// Discord Interactions: the application's public key, as shown in the
+    /// developer portal. The slash command endpoint is disabled without it.
+    #[clap(long, env = "DISCORD_PUBLIC_KEY")]
+    pub discord_public_key: Option<String>,
+
     /// Discord Webhook Bot: ID
     #[clap(long, env = "DISCORD_WEBHOOK_ID")]
-    pub discord_webhook_id: String,
+    pub discord_webhook_id: Option<String>,
 
     /// Discord Webhook Bot: Token
     #[clap(long, env = "DISCORD_WEBHOOK_TOKEN")]
-    pub discord_webhook_token: String,
+    pub discord_webhook_token: Option<String>,
+
+    /// Path to the file the importer reads levels from, if the import source
+    /// is a file-based one
+    #[clap(long, env = "IMPORT_FILE")]
+    pub import_file: Option<PathBuf>,
+
+    /// Where the importer should get its levels from
+    #[clap(value_enum, long, env = "IMPORT_SOURCE", default_value_t = ImportSourceKind::Mssql)]
+    pub import_source: ImportSourceKind,
+
+    /// Importer: how many levels are written to the database per query
+    #[clap(long, env = "IMPORT_BATCH_SIZE", default_value_t = 1000)]
+    pub import_batch_size: usize,
+
+    /// Importer: aborts the import if the total number of levels would drop by
+    /// more than this percentage
+    #[clap(long, env = "IMPORT_MAX_DROP_PERCENT", default_value_t = 25.0)]
+    pub import_max_drop_percent: f64,
+
+    /// Importer: aborts the import if the number of levels of any single year
+    /// would drop by more than this percentage
+    #[clap(long, env = "IMPORT_MAX_YEAR_DROP_PERCENT", default_value_t = 50.0)]
+    pub import_max_year_drop_percent: f64,
+
+    /// Importer: aborts the import if upstream has fewer levels than this
+    #[clap(long, env = "IMPORT_MIN_LEVELS", default_value_t = 1)]
+    pub import_min_levels: usize,
+
+    /// Importer: for how many seconds levels that were verified as cleared
+    /// or removed by hand are kept out of the randomizer, even if upstream
+    /// still lists them. After that, upstream is trusted again.
+    #[clap(long, env = "IMPORT_ARCHIVE_HOLD_SECS", default_value_t = 604800)]
+    pub import_archive_hold_secs: u32,
+
+    /// Level index: keep all SMM2 levels in memory and pick random levels
+    /// from there, instead of asking the database every time
+    #[clap(long, env = "LEVEL_INDEX")]
+    pub level_index: bool,
+
+    /// Level index: how often to check whether levels were imported or
+    /// archived since the index was loaded. Changes are announced with
+    /// `NOTIFY` as well, so this only matters if one of those got lost
+    #[clap(long, env = "LEVEL_INDEX_REFRESH_SECS", default_value_t = 300)]
+    pub level_index_refresh_secs: u32,
+
+    /// Clear verification: base URL of a TheGreatRambler-style level-data
+    /// API, like `https://tgrcode.com/mm2`. If set, SMM2 clear reports are
+    /// checked against the level's clear count. Reports for levels without
+    /// clears are withheld, and confirmed clears are archived right away
+    #[clap(long, env = "LEVEL_VERIFICATION_BASE_URL")]
+    pub level_verification_base_url: Option<reqwest::Url>,
 
     /// The Socket address the server should listen on
     #[clap(long, env = "LISTEN", default_value = "[::1]:8081")]
@@ -62,11 +157,56 @@ pub struct Settings {
     #[clap(value_enum, long, env = "LOG_LEVEL", default_value_t = LogLevel::Info)]
     pub log_level: LogLevel,
 
+    /// Where clear reports get delivered to. Defaults to `discord` if the
+    /// Discord webhook is configured, and to `log` otherwise
+    #[clap(value_enum, long, env = "NOTIFIER")]
+    pub notifier: Option<NotifierKind>,
+
+    /// The URL clear reports are posted to with the `json-webhook` notifier
+    #[clap(long, env = "NOTIFIER_WEBHOOK_URL")]
+    pub notifier_webhook_url: Option<reqwest::Url>,
+
     /// The public URL, including protocol and port, where this application is
     /// reachable to users, like `https://app.exmaple.com`
     #[clap(long, env = "PUBLIC_URL")]
     pub public_url: reqwest::Url,
 
+    /// Rate limiting: how many requests an authenticated API source can
+    /// make in a burst, per group of routes
+    #[clap(long, env = "RATE_LIMIT_API_SOURCE_BURST", default_value_t = 60)]
+    pub rate_limit_api_source_burst: u32,
+
+    /// Rate limiting: how many requests per minute an authenticated API
+    /// source can make on average. `0` disables the limit
+    #[clap(long, env = "RATE_LIMIT_API_SOURCE_PER_MINUTE", default_value_t = 120)]
+    pub rate_limit_api_source_per_minute: u32,
+
+    /// Rate limiting: the name of a header with the client's IP address, like
+    /// `X-Forwarded-For`, if the app runs behind a reverse proxy. If the
+    /// header has a list of addresses, the last one is used
+    #[clap(long, env = "RATE_LIMIT_CLIENT_IP_HEADER")]
+    pub rate_limit_client_ip_header: Option<String>,
+
+    /// Rate limiting: how many random levels a single IP can request in a
+    /// burst
+    #[clap(long, env = "RATE_LIMIT_RANDOM_BURST", default_value_t = 30)]
+    pub rate_limit_random_burst: u32,
+
+    /// Rate limiting: how many random levels per minute a single IP can
+    /// request on average. `0` disables the limit
+    #[clap(long, env = "RATE_LIMIT_RANDOM_PER_MINUTE", default_value_t = 60)]
+    pub rate_limit_random_per_minute: u32,
+
+    /// Rate limiting: how many clear or problem reports a single IP can send
+    /// in a burst
+    #[clap(long, env = "RATE_LIMIT_REPORTS_BURST", default_value_t = 5)]
+    pub rate_limit_reports_burst: u32,
+
+    /// Rate limiting: how many clear or problem reports per minute a single IP
+    /// can send on average. `0` disables the limit
+    #[clap(long, env = "RATE_LIMIT_REPORTS_PER_MINUTE", default_value_t = 6)]
+    pub rate_limit_reports_per_minute: u32,
+
     /// Limits the number of threads used - defaults to the number of CPU cores
     #[clap(long, env = "THREADS")]
     pub threads: Option<usize>,
@@ -75,3 +215,19 @@ pub struct Settings {
     #[clap(long, env = "UPSTREAM_DB_CONNSTRING")]
     pub upstream_db_connstring: Option<String>,
 }
+
+impl Settings {
+    /// The configured [NotifierKind], or the default for the rest of the
+    /// configuration.
+    pub fn notifier_kind(&self) -> NotifierKind {
+        match (
+            self.notifier,
+            &self.discord_webhook_id,
+            &self.discord_webhook_token,
+        ) {
+            (Some(kind), _, _) => kind,
+            (None, Some(_), Some(_)) => NotifierKind::Discord,
+            (None, _, _) => NotifierKind::Log,
+        }
+    }
+}
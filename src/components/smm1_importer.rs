@@ -0,0 +1,100 @@
+use sqlx::{PgConnection, postgres::PgQueryResult};
+use time::OffsetDateTime;
+
+use crate::{
+    components::{
+        import_source::RawSmm1Level,
+        importer::{ImportableLevel, LevelConversionError},
+        upstream_upload_datetime,
+    },
+    entities::{level::Level, smm1_level::Smm1Level},
+};
+
+impl TryFrom<&RawSmm1Level> for Smm1Level {
+    type Error = LevelConversionError;
+
+    fn try_from(value: &RawSmm1Level) -> Result<Self, Self::Error> {
+        let uploaded_at = upstream_upload_datetime(not_null!(value, date));
+
+        Ok(Smm1Level {
+            id: Smm1Level::normalized_internal_level_id(&not_null!(value, id)),
+            year: uploaded_at.year() as i64,
+            title: not_null!(value, name),
+            uploaded_at,
+            attempts: not_null!(value, attempts),
+            footprints: not_null!(value, footprints),
+            likes: not_null!(value, likes),
+            style: not_null!(value, style).trim().to_uppercase(),
+        })
+    }
+}
+
+impl ImportableLevel for Smm1Level {
+    type Raw = RawSmm1Level;
+
+    fn raw_level_id(raw: &RawSmm1Level) -> Option<&str> {
+        raw.id.as_deref()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn year(&self) -> i64 {
+        self.year
+    }
+
+    fn style(&self) -> &str {
+        &self.style
+    }
+
+    async fn get_all(conn: &mut PgConnection) -> Result<Vec<Smm1Level>, sqlx::Error> {
+        Smm1Level::get_all(conn).await
+    }
+
+    async fn update_many(
+        conn: &mut PgConnection,
+        levels: &[Smm1Level],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        Smm1Level::update_many(conn, levels).await
+    }
+
+    async fn reveal_pending_clears(
+        conn: &mut PgConnection,
+        fetched_at: OffsetDateTime,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        Smm1Level::reveal_pending_clears(conn, fetched_at).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn level_conversion_normalizes_fields() {
+        let raw = RawSmm1Level {
+            id: Some("4A6F-0000-0123-ABCD".to_string()),
+            date: Some(datetime!(2016-03-21 07:05:00.1234567)),
+            name: Some("Level".to_string()),
+            attempts: Some(87),
+            footprints: Some(12),
+            likes: Some(0),
+            style: Some("smw".to_string()),
+        };
+
+        let level = Smm1Level::try_from(&raw).unwrap();
+        assert_eq!(level.id, "4a6f00000123abcd");
+        assert_eq!(level.year, 2016);
+        assert_eq!(level.uploaded_at, datetime!(2016-03-21 07:05:00.123456 UTC));
+        assert_eq!(level.style, "SMW");
+
+        let raw = RawSmm1Level { likes: None, ..raw };
+        assert_eq!(
+            Smm1Level::try_from(&raw),
+            Err(LevelConversionError::MissingField("likes"))
+        );
+    }
+}
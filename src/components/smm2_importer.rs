@@ -1,27 +1,25 @@
-use std::collections::HashSet;
-
-use futures_util::TryStreamExt;
-use sqlx::PgPool;
-use time::{PrimitiveDateTime, macros::offset};
-use tracing::info;
-
-use crate::entities::smm2_level::Smm2Level;
-
-macro_rules! expect_not_null {
-    ($value:expr, $field:expr) => {
-        $value
-            .get($field)
-            .expect(&format!("{} IS NOT NULL", $field))
-    };
-    ($value:expr, $field:expr, $type:ty) => {
-        $value
-            .get::<$type, &str>($field)
-            .expect(&format!("{} IS NOT NULL", $field))
-    };
-}
-
-fn normalized_id(id: &str) -> String {
-    id.trim().replace('-', "").to_lowercase()
+use sqlx::{PgConnection, postgres::PgQueryResult};
+use time::OffsetDateTime;
+
+use crate::{
+    components::{
+        import_source::RawSmm2Level,
+        importer::{ImportableLevel, LevelConversionError},
+        upstream_upload_datetime,
+    },
+    entities::{level::Level, smm2_level::Smm2Level},
+};
+
+macro_rules! numeric_string {
+    ($value:expr, $field:ident) => {{
+        let raw_value = not_null!($value, $field);
+        raw_value
+            .parse::<i64>()
+            .map_err(|_| LevelConversionError::InvalidNumber {
+                field: stringify!($field),
+                value: raw_value,
+            })?
+    }};
 }
 
 fn normalized_tags(tag1: Option<&str>, tag2: Option<&str>) -> Vec<String> {
@@ -40,115 +38,148 @@ fn normalize_tag_name(name: &str) -> String {
     name.trim().replace(' ', "_").to_lowercase()
 }
 
-impl From<tiberius::Row> for Smm2Level {
-    fn from(value: tiberius::Row) -> Self {
-        let id: &str = expect_not_null!(value, "id");
-
-        // Cryptan stored the upload datetimes without offset. It's kinda
-        // irrelevant for our use-case, so I'll just assume they're always UTC.
-        let naive_datetime: PrimitiveDateTime = expect_not_null!(value, "date");
-        let uploaded_at = naive_datetime.assume_offset(offset!(UTC));
+impl TryFrom<&RawSmm2Level> for Smm2Level {
+    type Error = LevelConversionError;
 
-        let title: &str = expect_not_null!(value, "name");
-        let description = value.get::<&str, &str>("description").map(|s| s.to_owned());
-
-        let clearcheck_ms: i32 = expect_not_null!(value, "upload_time");
-        let attempts: i32 = expect_not_null!(value, "attempts");
-        let footprints: i32 = expect_not_null!(value, "footprints");
+    fn try_from(value: &RawSmm2Level) -> Result<Self, Self::Error> {
+        let uploaded_at = upstream_upload_datetime(not_null!(value, date));
 
         // Please do not ask me why those fields are strings...
-        let likes: i64 = expect_not_null!(value, "likes", &str)
-            .parse::<i64>()
-            .expect("field should be a numeric value");
-        let boos: i64 = expect_not_null!(value, "boos", &str)
-            .parse::<i64>()
-            .expect("field should be a numeric value");
-        let comments: i64 = expect_not_null!(value, "comments", &str)
-            .parse::<i64>()
-            .expect("field should be a numeric value");
+        let likes = numeric_string!(value, likes);
+        let boos = numeric_string!(value, boos);
+        let comments = numeric_string!(value, comments);
 
-        let clear_condition = match value.get::<i32, &str>("clear_condition") {
+        let clear_condition = match value.clear_condition {
             None => None,
             Some(0) => None,
-            Some(i) => Some(i as i64),
+            Some(i) => Some(i),
         };
-        let clear_condition_magnitude = match value.get::<i32, &str>("clear_condition_magnitude") {
+        let clear_condition_magnitude = match value.clear_condition_magnitude {
             None => None,
             Some(0) => None,
-            Some(i) => Some(i as i64),
+            Some(i) => Some(i),
         };
 
-        let style: &str = expect_not_null!(value, "style");
-        let theme: &str = expect_not_null!(value, "theme");
-        let tags = normalized_tags(value.get("tag1"), value.get("tag2"));
+        let theme = not_null!(value, theme);
+        let tags = normalized_tags(value.tag1.as_deref(), value.tag2.as_deref());
 
-        Smm2Level {
-            id: normalized_id(id),
+        Ok(Smm2Level {
+            id: Smm2Level::normalized_internal_level_id(&not_null!(value, id)),
             year: uploaded_at.year() as i64,
-            title: title.to_owned(),
-            description,
+            title: not_null!(value, name),
+            description: value.description.clone(),
             uploaded_at,
-            clearcheck_ms: clearcheck_ms as i64,
-            attempts: attempts as i64,
-            footprints: footprints as i64,
+            clearcheck_ms: not_null!(value, upload_time),
+            attempts: not_null!(value, attempts),
+            footprints: not_null!(value, footprints),
             likes,
             boos,
             comments,
             clear_condition,
             clear_condition_magnitude,
-            style: style.to_owned(),
-            theme: normalize_tag_name(theme),
+            style: not_null!(value, style),
+            theme: normalize_tag_name(&theme),
             tags,
-        }
+        })
     }
 }
 
-#[tracing::instrument(skip(upstream_db, own_db))]
-pub async fn run(
-    upstream_db: &mut tiberius::Client<tokio_util::compat::Compat<tokio::net::TcpStream>>,
-    own_db: PgPool,
-) -> anyhow::Result<()> {
-    let level_blocklist: HashSet<String> =
-        sqlx::query_scalar!("SELECT level_id FROM level_blocklist WHERE game = 'smm2'")
-            .fetch_all(&own_db)
-            .await?
-            .iter()
-            .cloned()
-            .collect();
-
-    let mut db_transaction = own_db.begin().await?;
-
-    info!("deleting current levels...");
-    sqlx::query!("DELETE FROM levels_smm2")
-        .execute(&mut *db_transaction)
-        .await?;
-
-    info!("streaming levels...");
-    let mut levels_count = 0;
-    let mut rows = upstream_db
-        .simple_query("SELECT * FROM v_Uncleared")
-        .await?
-        .into_row_stream();
-    while let Some(row) = rows.try_next().await? {
-        let level: Smm2Level = row.into();
-
-        if level_blocklist.contains(&level.id) {
-            continue;
-        }
-
-        level.store(&mut *db_transaction).await?;
-        levels_count += 1;
+impl ImportableLevel for Smm2Level {
+    type Raw = RawSmm2Level;
+
+    fn raw_level_id(raw: &RawSmm2Level) -> Option<&str> {
+        raw.id.as_deref()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
     }
 
-    if levels_count > 0 {
-        info!("committing...");
-        db_transaction.commit().await?;
-        info!("done, imported {} levels!", levels_count);
-    } else {
-        info!("somehow got no levels, rolling back...");
-        db_transaction.rollback().await?;
-        info!("done!");
+    fn year(&self) -> i64 {
+        self.year
     }
 
-    Ok(())
+    fn style(&self) -> &str {
+        &self.style
+    }
+
+    async fn get_all(conn: &mut PgConnection) -> Result<Vec<Smm2Level>, sqlx::Error> {
+        Smm2Level::get_all(conn).await
+    }
+
+    async fn update_many(
+        conn: &mut PgConnection,
+        levels: &[Smm2Level],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        Smm2Level::update_many(conn, levels).await
+    }
+
+    async fn reveal_pending_clears(
+        conn: &mut PgConnection,
+        fetched_at: OffsetDateTime,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        Smm2Level::reveal_pending_clears(conn, fetched_at).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn level_conversion_normalizes_fields() {
+        let raw = RawSmm2Level {
+            id: Some(" 000-FBT-9KF ".to_owned()),
+            date: Some(time::macros::datetime!(2024-06-15 14:31:13.123456789)),
+            name: Some("Test Level".to_owned()),
+            upload_time: Some(19233),
+            attempts: Some(14),
+            footprints: Some(5),
+            likes: Some("0".to_owned()),
+            boos: Some("0".to_owned()),
+            comments: Some("0".to_owned()),
+            clear_condition: Some(0),
+            style: Some("NSMBU".to_owned()),
+            theme: Some("Ghost House".to_owned()),
+            tag1: Some("Speedrun".to_owned()),
+            tag2: Some("None".to_owned()),
+            ..Default::default()
+        };
+
+        let level = Smm2Level::try_from(&raw).unwrap();
+        assert_eq!(level.id, "000fbt9kf");
+        assert_eq!(level.year, 2024);
+        assert_eq!(level.uploaded_at, datetime!(2024-06-15 14:31:13.123456 UTC));
+        assert_eq!(level.clear_condition, None);
+        assert_eq!(level.theme, "ghost_house");
+        assert_eq!(level.tags, vec!["speedrun".to_owned()]);
+    }
+
+    #[test]
+    fn level_conversion_reports_broken_fields() {
+        let raw = RawSmm2Level {
+            id: Some("000fbt9kf".to_owned()),
+            date: Some(time::macros::datetime!(2024-06-15 14:31:13)),
+            likes: Some("many".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Smm2Level::try_from(&raw),
+            Err(LevelConversionError::InvalidNumber {
+                field: "likes",
+                value: "many".to_owned()
+            })
+        );
+
+        let raw = RawSmm2Level {
+            likes: Some("1".to_owned()),
+            ..raw
+        };
+        assert_eq!(
+            Smm2Level::try_from(&raw),
+            Err(LevelConversionError::MissingField("boos"))
+        );
+    }
 }
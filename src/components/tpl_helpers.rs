@@ -1,4 +1,4 @@
-use crate::entities::smm2_level::Smm2Level;
+use crate::entities::{level::Level, smm1_level::Smm1Level, smm2_level::Smm2Level};
 
 pub fn clear_condition_text(id: i64, magnitude: Option<i64>) -> String {
     Smm2Level::clear_condition_text(id, magnitude)
@@ -8,6 +8,10 @@ pub fn formatted_level_id(raw_id: &str) -> String {
     Smm2Level::formatted_level_id(raw_id)
 }
 
+pub fn formatted_smm1_level_id(raw_id: &str) -> String {
+    Smm1Level::formatted_level_id(raw_id)
+}
+
 pub fn ms_to_minsecs(ms: i64) -> String {
     let seconds = (ms as f64 / 1000.0).ceil();
     if seconds > 60.0 {
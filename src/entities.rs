@@ -1,2 +1,40 @@
-pub mod discord_webhook_source;
+/// Appends a `<check> <bound value>` condition to a [sqlx::QueryBuilder] if
+/// the given filter value is set. Shared between all the level entities.
+macro_rules! push_optional_filter {
+    ($builder:expr, $field_name:expr, $check:expr) => {
+        push_optional_filter!($builder, $field_name, $check, "");
+    };
+    ($builder:expr, $field_name:expr, $check_pre:expr, $check_post:expr) => {
+        if let Some(val) = $field_name {
+            $builder.push($check_pre);
+            $builder.push_bind(val);
+            $builder.push($check_post);
+        }
+    };
+}
+
+/// Like [push_optional_filter], for lists that are bound as an array, and
+/// skipped if they're empty.
+macro_rules! push_list_filter {
+    ($builder:expr, $values:expr, $check:expr) => {
+        push_list_filter!($builder, $values, $check, "");
+    };
+    ($builder:expr, $values:expr, $check_pre:expr, $check_post:expr) => {
+        if !$values.is_empty() {
+            $builder.push($check_pre);
+            $builder.push_bind($values);
+            $builder.push($check_post);
+        }
+    };
+}
+
+pub mod api_source;
+pub mod blocklist_entry;
+pub mod clear_report;
+pub mod import_run;
+pub mod level;
+pub mod level_archive;
+pub mod level_report;
+pub mod quarantined_level;
+pub mod smm1_level;
 pub mod smm2_level;
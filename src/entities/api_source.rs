@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgExecutor};
+use time::OffsetDateTime;
+
+/// A third-party tool that reports clears through the API. Sources
+/// authenticate with a token, and their `url` is linked in the notifications
+/// for their reports.
+#[derive(Clone, Debug, Serialize, Deserialize, FromRow)]
+pub struct ApiSource {
+    pub key: String,
+    pub display_name: String,
+    pub url: String,
+    pub enabled: bool,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    pub report_count: i64,
+}
+
+impl ApiSource {
+    pub async fn get<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        key: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            "SELECT key, display_name, url, enabled, created_at, last_used_at, report_count
+            FROM api_sources WHERE key = $1",
+            key
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    pub async fn get_by_token_hash<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        token_hash: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            "SELECT key, display_name, url, enabled, created_at, last_used_at, report_count
+            FROM api_sources WHERE token_hash = $1",
+            token_hash
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    /// Like [ApiSource::get], but only finds sources that were carried over
+    /// from before sources had tokens, and haven't been issued one yet.
+    pub async fn get_without_token<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        key: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            "SELECT key, display_name, url, enabled, created_at, last_used_at, report_count
+            FROM api_sources WHERE key = $1 AND token_hash IS NULL",
+            key
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    pub async fn get_all<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            "SELECT key, display_name, url, enabled, created_at, last_used_at, report_count
+            FROM api_sources ORDER BY key"
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn store<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        key: &str,
+        display_name: &str,
+        url: &str,
+        token_hash: &str,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            "INSERT INTO api_sources (key, display_name, url, token_hash)
+            VALUES ($1, $2, $3, $4)
+            RETURNING key, display_name, url, enabled, created_at, last_used_at, report_count",
+            key,
+            display_name,
+            url,
+            token_hash
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Replaces the source's token. Returns `false` if there's no such source.
+    pub async fn set_token_hash<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        key: &str,
+        token_hash: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE api_sources SET token_hash = $2 WHERE key = $1",
+            key,
+            token_hash
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enables or disables the source. Returns `false` if there's no such
+    /// source.
+    pub async fn set_enabled<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        key: &str,
+        enabled: bool,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE api_sources SET enabled = $2 WHERE key = $1",
+            key,
+            enabled
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn record_usage<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE api_sources SET report_count = report_count + 1, last_used_at = now()
+            WHERE key = $1",
+            self.key
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor};
+use time::OffsetDateTime;
+
+use crate::entities::level::Game;
+
+/// A single entry in the `level_blocklist`. Blocklisted levels never show up
+/// in the randomizer, no matter what upstream says.
+#[derive(Clone, Debug, PartialEq, Serialize, FromRow)]
+pub struct BlocklistEntry {
+    pub game: Game,
+    pub level_id: String,
+    pub reason: Option<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+impl BlocklistEntry {
+    /// Adds a level to the blocklist. If the level is already on it, only the
+    /// reason gets updated.
+    pub async fn store<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Game,
+        level_id: &str,
+        reason: Option<&str>,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"INSERT INTO level_blocklist (game, level_id, reason) VALUES ($1, $2, $3)
+            ON CONFLICT (game, level_id) DO UPDATE SET reason = EXCLUDED.reason
+            RETURNING game as "game: Game", level_id, reason, created_at"#,
+            game as Game,
+            level_id,
+            reason
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Removes a level from the blocklist. Returns `false` if there was no
+    /// such entry.
+    pub async fn delete<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Game,
+        level_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM level_blocklist WHERE game = $1 AND level_id = $2",
+            game as Game,
+            level_id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Lists all entries, newest first, optionally only for a single game.
+    pub async fn get_all<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Option<Game>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT game as "game: Game", level_id, reason, created_at
+            FROM level_blocklist
+            WHERE $1::text IS NULL OR game = $1
+            ORDER BY created_at DESC, level_id"#,
+            game as Option<Game>
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
@@ -0,0 +1,419 @@
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgExecutor};
+use time::{Duration, OffsetDateTime};
+
+use crate::entities::level::Game;
+
+/// How a clear report got to us.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ReportChannel {
+    /// The "Mark as Cleared" button on the website.
+    Web,
+    /// The JSON API, used by bots and other tools.
+    Api,
+    /// The `/cleared` slash command.
+    Discord,
+}
+
+/// Whether the clear report has been delivered by the notifier yet.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not attempted yet.
+    Pending,
+    Delivered,
+    /// At least one attempt failed, but it will be retried.
+    Failed,
+    /// Failed too often, and won't be retried unless an admin requeues it.
+    DeadLettered,
+    /// A duplicate of a recent report for the same level, so it's not
+    /// delivered on its own. See [ClearReport::duplicate_of].
+    Coalesced,
+    /// The level-data API says nobody cleared the level, so the report isn't
+    /// delivered unless an admin requeues it.
+    Withheld,
+}
+
+/// What the level-data API had to say about a clear report.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum VerificationStatus {
+    /// The level has at least one clear upstream.
+    Verified,
+    /// The API couldn't tell, because it failed or doesn't know the level.
+    Unverified,
+    /// The level has no clears upstream.
+    Contradicted,
+}
+
+/// The outcome of checking a report against the level-data API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Verification {
+    pub status: VerificationStatus,
+    pub upstream_clears: Option<i64>,
+}
+
+/// Whatever the client told us about itself. This is only used to make sense
+/// of weird reports later, so none of it is required.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ClientHints {
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+}
+
+/// A single "I cleared this level" report, stored in `clear_reports`. Every
+/// report gets stored before anything is delivered, so there's a record
+/// even if the delivery fails.
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct ClearReport {
+    pub id: i64,
+    pub game: Game,
+    pub level_id: String,
+    pub source: Option<String>,
+    pub channel: ReportChannel,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+
+    pub delivery_status: DeliveryStatus,
+    pub delivery_attempts: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub delivered_at: Option<OffsetDateTime>,
+    pub last_delivery_error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub next_delivery_attempt_at: OffsetDateTime,
+
+    /// The report this one was coalesced into, if it's a duplicate.
+    pub duplicate_of: Option<i64>,
+    /// How many duplicates were coalesced into this report.
+    pub confirmations: i32,
+
+    /// `None` if the report wasn't checked against the level-data API.
+    pub verification_status: Option<VerificationStatus>,
+    pub upstream_clears: Option<i64>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub verified_at: Option<OffsetDateTime>,
+}
+
+/// Everything needed to store a new [ClearReport].
+#[derive(Clone, Debug)]
+pub struct NewClearReport<'a> {
+    pub game: Game,
+    pub level_id: &'a str,
+    pub source: Option<&'a str>,
+    pub channel: ReportChannel,
+    pub client_hints: &'a ClientHints,
+    pub duplicate_of: Option<i64>,
+    pub verification: Option<Verification>,
+}
+
+impl ClearReport {
+    /// Stores a new report. Duplicates are stored as [DeliveryStatus::Coalesced]
+    /// and contradicted reports as [DeliveryStatus::Withheld] right away, so
+    /// the delivery worker never sees them.
+    pub async fn store<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        report: &NewClearReport<'_>,
+    ) -> Result<Self, sqlx::Error> {
+        let verification_status = report.verification.map(|v| v.status);
+        let delivery_status = match (report.duplicate_of, verification_status) {
+            (Some(_), _) => DeliveryStatus::Coalesced,
+            (None, Some(VerificationStatus::Contradicted)) => DeliveryStatus::Withheld,
+            (None, _) => DeliveryStatus::Pending,
+        };
+
+        sqlx::query_as!(
+            Self,
+            r#"INSERT INTO clear_reports
+                (game, level_id, source, channel, user_agent, referer, duplicate_of, delivery_status,
+                verification_status, upstream_clears, verified_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, CASE WHEN $9::text IS NULL THEN NULL ELSE now() END)
+            RETURNING
+                id,
+                game as "game: Game",
+                level_id,
+                source,
+                channel as "channel: ReportChannel",
+                user_agent,
+                referer,
+                created_at,
+                delivery_status as "delivery_status: DeliveryStatus",
+                delivery_attempts,
+                delivered_at,
+                last_delivery_error,
+                next_delivery_attempt_at,
+                duplicate_of,
+                confirmations,
+                verification_status as "verification_status: VerificationStatus",
+                upstream_clears,
+                verified_at"#,
+            report.game as Game,
+            report.level_id,
+            report.source,
+            report.channel as ReportChannel,
+            report.client_hints.user_agent,
+            report.client_hints.referer,
+            report.duplicate_of,
+            delivery_status as DeliveryStatus,
+            verification_status as Option<VerificationStatus>,
+            report.verification.and_then(|v| v.upstream_clears)
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Makes sure only one report for the given level is processed at a time,
+    /// until the transaction `conn` is in ends. Without this, two reports
+    /// coming in at the same time would both not see each other.
+    pub async fn lock_level(
+        conn: &mut PgConnection,
+        game: Game,
+        level_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "SELECT pg_advisory_xact_lock(hashtextextended('clear_reports:' || $1 || ':' || $2, 0))",
+            game as Game,
+            level_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Finds the report that new reports for the given level would be
+    /// duplicates of: the latest one that isn't a duplicate itself, if it's
+    /// younger than `window`. Dead-lettered and withheld reports don't count,
+    /// since nobody heard about them.
+    pub async fn find_recent_original<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Game,
+        level_id: &str,
+        window: Duration,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                game as "game: Game",
+                level_id,
+                source,
+                channel as "channel: ReportChannel",
+                user_agent,
+                referer,
+                created_at,
+                delivery_status as "delivery_status: DeliveryStatus",
+                delivery_attempts,
+                delivered_at,
+                last_delivery_error,
+                next_delivery_attempt_at,
+                duplicate_of,
+                confirmations,
+                verification_status as "verification_status: VerificationStatus",
+                upstream_clears,
+                verified_at
+            FROM clear_reports
+            WHERE
+                game = $1
+                AND level_id = $2
+                AND duplicate_of IS NULL
+                AND delivery_status NOT IN ('coalesced', 'dead_lettered', 'withheld')
+                AND created_at > now() - make_interval(secs => $3)
+            ORDER BY created_at DESC
+            LIMIT 1"#,
+            game as Game,
+            level_id,
+            window.as_seconds_f64()
+        )
+        .fetch_optional(executor)
+        .await
+    }
+
+    pub async fn add_confirmation<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE clear_reports SET confirmations = confirmations + 1 WHERE id = $1",
+            self.id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claims up to `limit` reports that are due for delivery. Claimed reports
+    /// are pushed back by `lease`, so other workers leave them alone, and so
+    /// they are picked up again if the worker dies halfway through.
+    pub async fn claim_due<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"UPDATE clear_reports SET next_delivery_attempt_at = now() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM clear_reports
+                WHERE delivery_status IN ('pending', 'failed') AND next_delivery_attempt_at <= now()
+                ORDER BY next_delivery_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING
+                id,
+                game as "game: Game",
+                level_id,
+                source,
+                channel as "channel: ReportChannel",
+                user_agent,
+                referer,
+                created_at,
+                delivery_status as "delivery_status: DeliveryStatus",
+                delivery_attempts,
+                delivered_at,
+                last_delivery_error,
+                next_delivery_attempt_at,
+                duplicate_of,
+                confirmations,
+                verification_status as "verification_status: VerificationStatus",
+                upstream_clears,
+                verified_at"#,
+            limit,
+            lease.as_seconds_f64()
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    pub async fn mark_delivered<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE clear_reports SET
+                delivery_status = 'delivered',
+                delivery_attempts = delivery_attempts + 1,
+                delivered_at = now(),
+                last_delivery_error = NULL
+            WHERE id = $1",
+            self.id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records a failed delivery attempt. The report is retried at
+    /// `retry_at`, or dead-lettered if that's `None`.
+    pub async fn mark_failed<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+        error: &str,
+        retry_at: Option<OffsetDateTime>,
+    ) -> Result<(), sqlx::Error> {
+        let status = match retry_at {
+            Some(_) => DeliveryStatus::Failed,
+            None => DeliveryStatus::DeadLettered,
+        };
+
+        sqlx::query!(
+            "UPDATE clear_reports SET
+                delivery_status = $2,
+                delivery_attempts = delivery_attempts + 1,
+                last_delivery_error = $3,
+                next_delivery_attempt_at = COALESCE($4, next_delivery_attempt_at)
+            WHERE id = $1",
+            self.id,
+            status as DeliveryStatus,
+            error,
+            retry_at
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Puts the report back in line without counting the attempt, for when
+    /// the notifier asked us to back off.
+    pub async fn postpone<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+        retry_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE clear_reports SET next_delivery_attempt_at = $2 WHERE id = $1",
+            self.id,
+            retry_at
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns all reports with the given delivery status, newest first. Used
+    /// to find reports that need an admin, see [Self::requeue].
+    pub async fn get_by_delivery_status<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        delivery_status: DeliveryStatus,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                game as "game: Game",
+                level_id,
+                source,
+                channel as "channel: ReportChannel",
+                user_agent,
+                referer,
+                created_at,
+                delivery_status as "delivery_status: DeliveryStatus",
+                delivery_attempts,
+                delivered_at,
+                last_delivery_error,
+                next_delivery_attempt_at,
+                duplicate_of,
+                confirmations,
+                verification_status as "verification_status: VerificationStatus",
+                upstream_clears,
+                verified_at
+            FROM clear_reports
+            WHERE delivery_status = $1
+            ORDER BY created_at DESC"#,
+            delivery_status as DeliveryStatus
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Gives a dead-lettered or withheld report a fresh set of attempts.
+    /// Returns `false` if there's no such report with that ID.
+    pub async fn requeue<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        id: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE clear_reports SET
+                delivery_status = 'pending',
+                delivery_attempts = 0,
+                next_delivery_attempt_at = now()
+            WHERE id = $1 AND delivery_status IN ('dead_lettered', 'withheld')",
+            id
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
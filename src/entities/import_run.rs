@@ -0,0 +1,150 @@
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor};
+use time::OffsetDateTime;
+
+use crate::{components::importer::ImportSummary, entities::level::Game};
+
+/// How an import run ended. Runs that are still in progress (or that died
+/// without getting the chance to clean up after themselves) have no outcome.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Committed,
+    RolledBack,
+}
+
+/// A single run of the importer, stored in `import_runs`. This is mostly here
+/// so we can answer "how fresh is the data?" without digging through logs.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ImportRun {
+    pub id: i64,
+    pub game: Game,
+    pub source: String,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    pub outcome: Option<ImportOutcome>,
+
+    pub added: i64,
+    pub updated: i64,
+    pub removed: i64,
+    pub unchanged: i64,
+    pub skipped_blocklisted: i64,
+    pub quarantined: i64,
+
+    /// The raw error of a failed run. It can contain hostnames and queries,
+    /// so it's not part of the public `/importz` output.
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl ImportRun {
+    pub async fn start<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Game,
+        source: &str,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"INSERT INTO import_runs (game, source) VALUES ($1, $2)
+            RETURNING
+                id,
+                game as "game: Game",
+                source,
+                started_at,
+                finished_at,
+                outcome as "outcome: ImportOutcome",
+                added,
+                updated,
+                removed,
+                unchanged,
+                skipped_blocklisted,
+                quarantined,
+                error"#,
+            game as Game,
+            source
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    pub async fn finish<'a, Executor: PgExecutor<'a>>(
+        &self,
+        executor: Executor,
+        outcome: ImportOutcome,
+        summary: &ImportSummary,
+        error: Option<&str>,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"UPDATE import_runs SET
+                finished_at = now(),
+                outcome = $2,
+                added = $3,
+                updated = $4,
+                removed = $5,
+                unchanged = $6,
+                skipped_blocklisted = $7,
+                quarantined = $8,
+                error = $9
+            WHERE id = $1
+            RETURNING
+                id,
+                game as "game: Game",
+                source,
+                started_at,
+                finished_at,
+                outcome as "outcome: ImportOutcome",
+                added,
+                updated,
+                removed,
+                unchanged,
+                skipped_blocklisted,
+                quarantined,
+                error"#,
+            self.id,
+            outcome as ImportOutcome,
+            summary.added as i64,
+            summary.updated as i64,
+            summary.removed as i64,
+            summary.unchanged as i64,
+            summary.skipped_blocklisted as i64,
+            summary.quarantined as i64,
+            error
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    pub async fn get_latest<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                id,
+                game as "game: Game",
+                source,
+                started_at,
+                finished_at,
+                outcome as "outcome: ImportOutcome",
+                added,
+                updated,
+                removed,
+                unchanged,
+                skipped_blocklisted,
+                quarantined,
+                error
+            FROM import_runs
+            ORDER BY started_at DESC
+            LIMIT $1"#,
+            limit
+        )
+        .fetch_all(executor)
+        .await
+    }
+}
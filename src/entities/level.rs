@@ -0,0 +1,268 @@
+use std::{collections::HashSet, fmt::Debug, future::Future, str::FromStr};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sqlx::{
+    FromRow, PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder, postgres::PgQueryResult,
+    postgres::PgRow,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    components::level_index::{IndexedPick, LevelIndex},
+    entities::{level_archive::RemovalReason, smm1_level::Smm1Level, smm2_level::Smm2Level},
+};
+
+/// All the games we have levels for. This is what ends up in the `game`
+/// columns all over the database, as well as in URLs like
+/// `/smm2/random_level/`.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Game {
+    Smm1,
+    Smm2,
+}
+
+impl Game {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Smm1 => "smm1",
+            Self::Smm2 => "smm2",
+        }
+    }
+
+    /// Formats a level ID the way this game displays it, for places that only
+    /// know the game at runtime.
+    pub fn formatted_level_id(self, raw_id: &str) -> String {
+        match self {
+            Self::Smm1 => Smm1Level::formatted_level_id(raw_id),
+            Self::Smm2 => Smm2Level::formatted_level_id(raw_id),
+        }
+    }
+
+    /// Returns the IDs of all levels of this game that are on the
+    /// `level_blocklist`.
+    pub async fn blocklisted_ids<'a, Executor: PgExecutor<'a>>(
+        self,
+        executor: Executor,
+    ) -> Result<HashSet<String>, sqlx::Error> {
+        Ok(sqlx::query_scalar!(
+            "SELECT level_id FROM level_blocklist WHERE game = $1",
+            self as Game
+        )
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .collect())
+    }
+
+    /// Returns the IDs of all levels of this game that were archived before
+    /// upstream noticed they're gone, after `removed_after`: verified clears,
+    /// and levels removed by hand. Upstream can take a while to catch up, so
+    /// the importer must not bring them back in the meantime. It only waits
+    /// so long, though, since the verification or the person removing the
+    /// level might have been wrong.
+    pub async fn archived_ahead_of_upstream_ids<'a, Executor: PgExecutor<'a>>(
+        self,
+        executor: Executor,
+        removed_after: OffsetDateTime,
+    ) -> Result<HashSet<String>, sqlx::Error> {
+        let ids = match self {
+            Self::Smm1 => {
+                sqlx::query_scalar!(
+                    "SELECT DISTINCT id FROM levels_smm1_archive
+                    WHERE
+                        removal_reason IN ('verified_cleared', 'manually_removed')
+                        AND removed_at > $1",
+                    removed_after
+                )
+                .fetch_all(executor)
+                .await?
+            }
+            Self::Smm2 => {
+                sqlx::query_scalar!(
+                    "SELECT DISTINCT id FROM levels_smm2_archive
+                    WHERE
+                        removal_reason IN ('verified_cleared', 'manually_removed')
+                        AND removed_at > $1",
+                    removed_after
+                )
+                .fetch_all(executor)
+                .await?
+            }
+        };
+
+        Ok(ids.into_iter().collect())
+    }
+}
+
+impl std::fmt::Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "smm1" => Ok(Self::Smm1),
+            "smm2" => Ok(Self::Smm2),
+            _ => Err(format!("unknown game `{s}`")),
+        }
+    }
+}
+
+/// Everything the app needs to know about the levels of a single [Game]. All
+/// the game-generic routes are built on top of this, so adding another game is
+/// a matter of adding a table, an entity, and an implementation of this.
+///
+/// Methods that get called from generic route handlers take a pool or a
+/// connection instead of a generic executor. The compiler can't prove the
+/// returned futures to be `Send` for every executor lifetime yet
+/// (rust-lang/rust#100013), and axum needs them to be.
+pub trait Level: Serialize + Send + Sync + Unpin + for<'r> FromRow<'r, PgRow> {
+    const GAME: Game;
+
+    /// The filters that can be passed to [Self::get_random_level], usually
+    /// deserialized from the query string.
+    type Filter: Clone + Debug + DeserializeOwned + Serialize + Send + Sync;
+
+    /// Inserts a whole batch of levels. Conflicting IDs are ignored.
+    fn store_many<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        levels: &[Self],
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send;
+
+    /// Moves the levels with the given IDs out of the level table and into
+    /// the game's archive table.
+    fn archive_many(
+        conn: &mut PgConnection,
+        level_ids: &[String],
+        reason: RemovalReason,
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send;
+
+    /// Moves a level that was archived as verified cleared or removed by
+    /// hand back out of the archive, as long as it isn't in the level table
+    /// already. Only the most recent archive entry counts.
+    fn restore_archived(
+        conn: &mut PgConnection,
+        level_id: &str,
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send;
+
+    /// Picks a random level matching the filters, if there is one.
+    fn get_random_level(
+        db: &PgPool,
+        params: &Self::Filter,
+    ) -> impl Future<Output = Result<Option<Self>, sqlx::Error>> + Send;
+
+    /// Counts the levels [Self::get_random_level] would pick from.
+    fn count_levels(
+        db: &PgPool,
+        params: &Self::Filter,
+    ) -> impl Future<Output = Result<i64, sqlx::Error>> + Send;
+
+    /// Picks a random level from the in-memory [LevelIndex], if this game has
+    /// one. `None` means the database has to be asked instead.
+    fn pick_from_index(_index: &LevelIndex, _params: &Self::Filter) -> Option<IndexedPick<Self>> {
+        None
+    }
+
+    /// Counts the matching levels in the in-memory [LevelIndex], if this game
+    /// has one. `None` means the database has to be asked instead.
+    fn count_in_index(_index: &LevelIndex, _params: &Self::Filter) -> Option<usize> {
+        None
+    }
+
+    fn id_exists(db: &PgPool, level_id: &str) -> impl Future<Output = bool> + Send;
+
+    /// Hides a level from [Self::get_random_level] until `until`, because
+    /// someone reported it as cleared.
+    fn hide_pending_clear(
+        conn: &mut PgConnection,
+        level_id: &str,
+        until: OffsetDateTime,
+    ) -> impl Future<Output = Result<PgQueryResult, sqlx::Error>> + Send;
+
+    /// Turns a user-provided level ID into the format used in the database.
+    fn normalized_internal_level_id(raw_id: &str) -> String {
+        raw_id.trim().replace('-', "").to_lowercase()
+    }
+
+    /// Checks if a normalized level ID has the right shape for this game.
+    fn is_valid_level_id(normalized_id: &str) -> bool;
+
+    /// Formats a level ID the way the game displays it.
+    fn formatted_level_id(raw_id: &str) -> String;
+
+    /// Gives the levels a chance to fill in defaults for filters the user did
+    /// not pick before a random level is selected. By default, filters are used
+    /// as they are.
+    fn effective_filters(params: &Self::Filter) -> Self::Filter {
+        params.clone()
+    }
+
+    /// Returns the IDs of all levels of this game that are on the
+    /// `level_blocklist`.
+    fn blocklisted_ids<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+    ) -> impl Future<Output = Result<HashSet<String>, sqlx::Error>> + Send {
+        Self::GAME.blocklisted_ids(executor)
+    }
+}
+
+/// Builds a query for a single, uniformly random level from `table` that
+/// matches the conditions added by `push_filters`, and isn't hidden as a
+/// pending clear.
+///
+/// Sorting the whole table by `random()` gets slow quickly, so this counts the
+/// matching levels and skips a random number of them instead. Both happen in
+/// the same statement, so they see the same snapshot and the offset can't
+/// run past the end. `push_filters` is called twice, once for each.
+pub(crate) fn random_level_query<'a>(
+    columns: &str,
+    table: &str,
+    push_filters: impl Fn(&mut QueryBuilder<'a, Postgres>),
+) -> QueryBuilder<'a, Postgres> {
+    let visible = "(pending_clear_until IS NULL OR pending_clear_until <= now())";
+
+    let mut query = QueryBuilder::new(format!("SELECT {columns} FROM {table} WHERE {visible}"));
+    push_filters(&mut query);
+    query.push(format!(
+        " OFFSET (SELECT floor(random() * count(*))::bigint FROM {table} WHERE {visible}"
+    ));
+    push_filters(&mut query);
+    query.push(") LIMIT 1");
+
+    query
+}
+
+/// Builds a query counting the levels in `table` that [random_level_query]
+/// would pick from.
+pub(crate) fn level_count_query<'a>(
+    table: &str,
+    push_filters: impl Fn(&mut QueryBuilder<'a, Postgres>),
+) -> QueryBuilder<'a, Postgres> {
+    let mut query = QueryBuilder::new(format!(
+        "SELECT count(*) FROM {table} WHERE (pending_clear_until IS NULL OR pending_clear_until <= now())"
+    ));
+    push_filters(&mut query);
+
+    query
+}
+
+/// Splits a normalized level ID into uppercase chunks of `chunk_size`, joined
+/// by dashes.
+pub(crate) fn chunked_level_id(raw_id: &str, chunk_size: usize) -> String {
+    raw_id
+        .to_uppercase()
+        .chars()
+        .collect::<Vec<char>>()
+        .chunks(chunk_size)
+        .map(|c| c.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("-")
+}
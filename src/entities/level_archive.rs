@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// Why a level left the set of uncleared levels and ended up in the archive.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// The level is gone from upstream, so someone cleared it.
+    UpstreamCleared,
+    /// The level was added to the `level_blocklist`.
+    Blocklisted,
+    /// Someone removed the level by hand.
+    ManuallyRemoved,
+    /// Someone reported a clear, and the level-data API confirmed it.
+    VerifiedCleared,
+}
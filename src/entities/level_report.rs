@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgExecutor};
+use time::OffsetDateTime;
+
+use crate::entities::level::Game;
+
+/// What's wrong with a reported level.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ReportCategory {
+    /// The level can't be cleared at all.
+    Impossible,
+    /// The level only works because of a glitch that doesn't work anymore.
+    Glitched,
+    /// The level can only be cleared with a developer exit.
+    DevExitOnly,
+    Other,
+}
+
+/// Where a report is in the moderation queue.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum ReportStatus {
+    Open,
+    /// A moderator agreed, and the level ended up on the blocklist.
+    Approved,
+    Rejected,
+}
+
+/// A single player report about a broken level, stored in `level_reports`.
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct LevelReport {
+    pub id: i64,
+    pub game: Game,
+    pub level_id: String,
+    pub category: ReportCategory,
+    pub note: Option<String>,
+    pub status: ReportStatus,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub resolved_at: Option<OffsetDateTime>,
+}
+
+/// All open reports for a single level, aggregated for the moderation queue.
+#[derive(Clone, Debug, Serialize, FromRow)]
+pub struct ReportedLevel {
+    pub game: Game,
+    pub level_id: String,
+    pub report_count: i64,
+    pub categories: Vec<String>,
+    pub notes: Vec<String>,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub first_reported_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_reported_at: OffsetDateTime,
+}
+
+impl LevelReport {
+    pub async fn store<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Game,
+        level_id: &str,
+        category: ReportCategory,
+        note: Option<&str>,
+    ) -> Result<Self, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"INSERT INTO level_reports (game, level_id, category, note) VALUES ($1, $2, $3, $4)
+            RETURNING
+                id,
+                game as "game: Game",
+                level_id,
+                category as "category: ReportCategory",
+                note,
+                status as "status: ReportStatus",
+                created_at,
+                resolved_at"#,
+            game as Game,
+            level_id,
+            category as ReportCategory,
+            note
+        )
+        .fetch_one(executor)
+        .await
+    }
+
+    /// Resolves all open reports for a level at once. Returns how many reports
+    /// were resolved.
+    pub async fn resolve_all<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Game,
+        level_id: &str,
+        status: ReportStatus,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE level_reports SET status = $3, resolved_at = now()
+            WHERE game = $1 AND level_id = $2 AND status = 'open'",
+            game as Game,
+            level_id,
+            status as ReportStatus
+        )
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+impl ReportedLevel {
+    /// Returns all levels with open reports, the most reported ones first.
+    /// Can be limited to a single game, or a single level of a game.
+    pub async fn get_open<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        game: Option<Game>,
+        level_id: Option<&str>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Self,
+            r#"SELECT
+                game as "game!: Game",
+                level_id as "level_id!",
+                count(*) as "report_count!",
+                array_agg(DISTINCT category ORDER BY category) as "categories!",
+                array_remove(array_agg(note ORDER BY created_at), NULL) as "notes!",
+                min(created_at) as "first_reported_at!",
+                max(created_at) as "last_reported_at!"
+            FROM level_reports
+            WHERE status = 'open'
+                AND ($1::text IS NULL OR game = $1)
+                AND ($2::text IS NULL OR level_id = $2)
+            GROUP BY game, level_id
+            ORDER BY count(*) DESC, max(created_at) DESC"#,
+            game as Option<Game>,
+            level_id
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// A short reason for the blocklist entry, if the reports get approved.
+    pub fn blocklist_reason(&self) -> String {
+        format!(
+            "approved community reports ({}x): {}",
+            self.report_count,
+            self.categories.join(", ")
+        )
+    }
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+use sqlx::{PgExecutor, postgres::PgQueryResult};
+
+/// An upstream row that the importer could not turn into a level, stored in
+/// `import_quarantine` together with the raw values so someone can take a look
+/// at what went wrong.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct QuarantinedLevel {
+    pub level_id: Option<String>,
+    pub raw: serde_json::Value,
+    pub error: String,
+}
+
+impl QuarantinedLevel {
+    pub async fn store_many<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        import_run_id: i64,
+        levels: &[QuarantinedLevel],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        let level_ids: Vec<Option<String>> = levels.iter().map(|l| l.level_id.clone()).collect();
+        let raws: Vec<serde_json::Value> = levels.iter().map(|l| l.raw.clone()).collect();
+        let errors: Vec<String> = levels.iter().map(|l| l.error.clone()).collect();
+
+        sqlx::query!(
+            "INSERT INTO import_quarantine (import_run_id, level_id, raw, error)
+            SELECT $1, level_id, raw, error
+            FROM UNNEST($2::text[], $3::jsonb[], $4::text[]) AS batch (level_id, raw, error)",
+            import_run_id,
+            &level_ids as &[Option<String>],
+            &raws,
+            &errors,
+        )
+        .execute(executor)
+        .await
+    }
+}
@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    FromRow, PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder, postgres::PgQueryResult,
+};
+use time::OffsetDateTime;
+
+use crate::{
+    components::deserializers::{empty_string_as_none, empty_string_as_none_enum},
+    entities::{
+        level::{Game, Level, chunked_level_id, level_count_query, random_level_query},
+        level_archive::RemovalReason,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct Smm1Level {
+    pub id: String,
+    pub year: i64,
+
+    pub title: String,
+    pub uploaded_at: OffsetDateTime,
+
+    pub attempts: i64,
+    pub footprints: i64,
+    pub likes: i64,
+
+    pub style: String,
+}
+
+impl Smm1Level {
+    /// Updates a whole batch of existing levels in a single round-trip. See
+    /// [Level::store_many] for how this works.
+    pub async fn update_many<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        levels: &[Smm1Level],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        let ids = levels.iter().map(|l| l.id.clone()).collect::<Vec<_>>();
+        let years = levels.iter().map(|l| l.year).collect::<Vec<_>>();
+        let titles = levels.iter().map(|l| l.title.clone()).collect::<Vec<_>>();
+        let uploaded_ats = levels.iter().map(|l| l.uploaded_at).collect::<Vec<_>>();
+        let attempts = levels.iter().map(|l| l.attempts).collect::<Vec<_>>();
+        let footprints = levels.iter().map(|l| l.footprints).collect::<Vec<_>>();
+        let likes = levels.iter().map(|l| l.likes).collect::<Vec<_>>();
+        let styles = levels.iter().map(|l| l.style.clone()).collect::<Vec<_>>();
+
+        sqlx::query!(
+            "UPDATE levels_smm1 SET
+                year = batch.year,
+                title = batch.title,
+                uploaded_at = batch.uploaded_at,
+                attempts = batch.attempts,
+                footprints = batch.footprints,
+                likes = batch.likes,
+                style = batch.style
+            FROM UNNEST(
+                $1::text[],
+                $2::bigint[],
+                $3::text[],
+                $4::timestamptz[],
+                $5::bigint[],
+                $6::bigint[],
+                $7::bigint[],
+                $8::text[]
+            ) AS batch (
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style
+            )
+            WHERE levels_smm1.id = batch.id",
+            &ids,
+            &years,
+            &titles,
+            &uploaded_ats,
+            &attempts,
+            &footprints,
+            &likes,
+            &styles,
+        )
+        .execute(executor)
+        .await
+    }
+
+    pub async fn get_all<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+    ) -> Result<Vec<Smm1Level>, sqlx::Error> {
+        sqlx::query_as!(
+            Smm1Level,
+            "SELECT
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style
+            FROM levels_smm1"
+        )
+        .fetch_all(executor)
+        .await
+    }
+
+    /// Brings back all levels hidden by [Level::hide_pending_clear] that
+    /// were still listed as uncleared by an import that fetched its levels at
+    /// `fetched_at`. Levels reported after that are left alone, since the
+    /// import can't know about those clears yet.
+    pub async fn reveal_pending_clears<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        fetched_at: OffsetDateTime,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE levels_smm1 SET pending_clear_until = NULL
+            WHERE
+                pending_clear_until IS NOT NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM clear_reports
+                    WHERE
+                        clear_reports.game = 'smm1'
+                        AND clear_reports.level_id = levels_smm1.id
+                        AND clear_reports.created_at >= $1
+                )",
+            fetched_at
+        )
+        .execute(executor)
+        .await
+    }
+}
+
+impl Level for Smm1Level {
+    const GAME: Game = Game::Smm1;
+    type Filter = FilterParams;
+
+    /// Inserts a whole batch of levels in a single round-trip, by passing
+    /// each column as an array and `UNNEST`ing them on the database side.
+    /// Conflicting IDs are ignored.
+    async fn store_many<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        levels: &[Smm1Level],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        let ids = levels.iter().map(|l| l.id.clone()).collect::<Vec<_>>();
+        let years = levels.iter().map(|l| l.year).collect::<Vec<_>>();
+        let titles = levels.iter().map(|l| l.title.clone()).collect::<Vec<_>>();
+        let uploaded_ats = levels.iter().map(|l| l.uploaded_at).collect::<Vec<_>>();
+        let attempts = levels.iter().map(|l| l.attempts).collect::<Vec<_>>();
+        let footprints = levels.iter().map(|l| l.footprints).collect::<Vec<_>>();
+        let likes = levels.iter().map(|l| l.likes).collect::<Vec<_>>();
+        let styles = levels.iter().map(|l| l.style.clone()).collect::<Vec<_>>();
+
+        sqlx::query!(
+            "INSERT INTO levels_smm1 (
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style
+            )
+            SELECT * FROM UNNEST(
+                $1::text[],
+                $2::bigint[],
+                $3::text[],
+                $4::timestamptz[],
+                $5::bigint[],
+                $6::bigint[],
+                $7::bigint[],
+                $8::text[]
+            )
+            ON CONFLICT DO NOTHING",
+            &ids,
+            &years,
+            &titles,
+            &uploaded_ats,
+            &attempts,
+            &footprints,
+            &likes,
+            &styles,
+        )
+        .execute(executor)
+        .await
+    }
+
+    async fn archive_many(
+        conn: &mut PgConnection,
+        level_ids: &[String],
+        reason: RemovalReason,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "WITH removed AS (
+                DELETE FROM levels_smm1 WHERE id = ANY($1) RETURNING *
+            )
+            INSERT INTO levels_smm1_archive (
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style,
+                first_seen_at,
+                removal_reason
+            )
+            SELECT
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style,
+                first_seen_at,
+                $2
+            FROM removed",
+            level_ids,
+            reason as RemovalReason,
+        )
+        .execute(conn)
+        .await
+    }
+
+    async fn restore_archived(
+        conn: &mut PgConnection,
+        level_id: &str,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "WITH restored AS (
+                DELETE FROM levels_smm1_archive
+                WHERE
+                    archive_id = (
+                        SELECT archive_id FROM levels_smm1_archive
+                        WHERE id = $1
+                        ORDER BY removed_at DESC, archive_id DESC
+                        LIMIT 1
+                    )
+                    AND removal_reason IN ('verified_cleared', 'manually_removed')
+                    AND NOT EXISTS (SELECT 1 FROM levels_smm1 WHERE id = $1)
+                RETURNING *
+            )
+            INSERT INTO levels_smm1 (
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style,
+                first_seen_at
+            )
+            SELECT
+                id,
+                year,
+                title,
+                uploaded_at,
+                attempts,
+                footprints,
+                likes,
+                style,
+                first_seen_at
+            FROM restored",
+            level_id
+        )
+        .execute(conn)
+        .await
+    }
+
+    async fn get_random_level(
+        db: &PgPool,
+        params: &FilterParams,
+    ) -> Result<Option<Smm1Level>, sqlx::Error> {
+        random_level_query(
+            "id, year, title, uploaded_at, attempts, footprints, likes, style",
+            "levels_smm1",
+            |query| params.push_conditions(query),
+        )
+        .build_query_as::<Smm1Level>()
+        .fetch_optional(db)
+        .await
+    }
+
+    async fn count_levels(db: &PgPool, params: &FilterParams) -> Result<i64, sqlx::Error> {
+        level_count_query("levels_smm1", |query| params.push_conditions(query))
+            .build_query_scalar()
+            .fetch_one(db)
+            .await
+    }
+
+    async fn id_exists(db: &PgPool, level_id: &str) -> bool {
+        sqlx::query!("SELECT id FROM levels_smm1 WHERE id = $1 LIMIT 1", level_id)
+            .fetch_optional(db)
+            .await
+            .is_ok_and(|r| r.is_some())
+    }
+
+    async fn hide_pending_clear(
+        conn: &mut PgConnection,
+        level_id: &str,
+        until: OffsetDateTime,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE levels_smm1 SET pending_clear_until = GREATEST(pending_clear_until, $2) WHERE id = $1",
+            level_id,
+            until
+        )
+        .execute(conn)
+        .await
+    }
+
+    /// SMM1 course IDs are 16 hex digits, shown in groups of four.
+    fn formatted_level_id(raw_id: &str) -> String {
+        chunked_level_id(raw_id, 4)
+    }
+
+    fn is_valid_level_id(normalized_id: &str) -> bool {
+        normalized_id.len() == 16
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "UPPERCASE")]
+pub enum Style {
+    SMB1,
+    SMB3,
+    SMW,
+    NSMBU,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FilterParams {
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub year: Option<i64>,
+
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub min_attempts: Option<i64>,
+
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub max_attempts: Option<i64>,
+
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub min_footprints: Option<i64>,
+
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub max_footprints: Option<i64>,
+
+    #[serde(default, deserialize_with = "empty_string_as_none_enum")]
+    pub style: Option<Style>,
+}
+
+impl FilterParams {
+    /// Appends an `AND ...` condition for every filter that is set.
+    fn push_conditions<'a>(&'a self, query: &mut QueryBuilder<'a, Postgres>) {
+        push_optional_filter!(query, self.year, " AND year = ");
+        push_optional_filter!(query, self.min_attempts, " AND attempts >= ");
+        push_optional_filter!(query, self.max_attempts, " AND attempts <= ");
+        push_optional_filter!(query, self.min_footprints, " AND footprints >= ");
+        push_optional_filter!(query, self.max_footprints, " AND footprints <= ");
+        push_optional_filter!(query, &self.style, " AND style = ");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn formatted_level_id_formats_correctly() {
+        assert_eq!(
+            Smm1Level::formatted_level_id("4a6f00000123abcd"),
+            "4A6F-0000-0123-ABCD"
+        );
+    }
+
+    /// Runs against the placeholder levels from the migrations.
+    #[sqlx::test]
+    async fn get_random_level_picks_uniformly(db: PgPool) {
+        const PICKS: usize = 8000;
+        let mut params = FilterParams {
+            year: None,
+            min_attempts: None,
+            max_attempts: None,
+            min_footprints: None,
+            max_footprints: None,
+            style: None,
+        };
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..PICKS {
+            let level = Smm1Level::get_random_level(&db, &params)
+                .await
+                .unwrap()
+                .unwrap();
+            *counts.entry(level.id).or_default() += 1;
+        }
+        assert_eq!(counts.len(), 8);
+
+        // Chi-squared with 7 degrees of freedom, so this fails by chance
+        // once in a thousand runs.
+        let expected = PICKS as f64 / counts.len() as f64;
+        let chi_squared: f64 = counts
+            .values()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < 24.32, "{counts:?}");
+
+        params.style = Some(Style::SMB1);
+        for _ in 0..50 {
+            let level = Smm1Level::get_random_level(&db, &params)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(level.style, "SMB1");
+        }
+
+        params.year = Some(2030);
+        assert!(
+            Smm1Level::get_random_level(&db, &params)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
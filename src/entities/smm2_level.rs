@@ -1,25 +1,25 @@
 use std::vec;
 
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgExecutor, Postgres, QueryBuilder, postgres::PgQueryResult};
+use sqlx::{
+    FromRow, PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder, postgres::PgQueryResult,
+};
 use time::OffsetDateTime;
 
-use crate::components::deserializers::{empty_string_as_none, empty_string_as_none_enum};
-
-macro_rules! push_optional_filter {
-    ($builder:expr, $field_name:expr, $check:expr) => {
-        push_optional_filter!($builder, $field_name, $check, "");
-    };
-    ($builder:expr, $field_name:expr, $check_pre:expr, $check_post:expr) => {
-        if let Some(val) = $field_name {
-            $builder.push($check_pre);
-            $builder.push_bind(val);
-            $builder.push($check_post);
-        }
-    };
-}
+use crate::{
+    components::{
+        deserializers::{
+            empty_string_as_none, empty_string_as_none_enum, empty_strings_skipped_enum,
+        },
+        level_index::{IndexedPick, LevelIndex},
+    },
+    entities::{
+        level::{Game, Level, chunked_level_id, level_count_query, random_level_query},
+        level_archive::RemovalReason,
+    },
+};
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, FromRow)]
 pub struct Smm2Level {
     pub id: String,
     pub year: i64,
@@ -89,12 +89,73 @@ impl Smm2Level {
         .await
     }
 
-    pub async fn get_random_level<'a, Executor: PgExecutor<'a>>(
+    /// Brings back all levels hidden by [Level::hide_pending_clear] that
+    /// were still listed as uncleared by an import that fetched its levels at
+    /// `fetched_at`. Levels reported after that are left alone, since the
+    /// import can't know about those clears yet.
+    pub async fn reveal_pending_clears<'a, Executor: PgExecutor<'a>>(
         executor: Executor,
-        params: &FilterParams,
-    ) -> Result<Option<Smm2Level>, sqlx::Error> {
-        let mut query = QueryBuilder::<Postgres>::new(
-            "SELECT
+        fetched_at: OffsetDateTime,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE levels_smm2 SET pending_clear_until = NULL
+            WHERE
+                pending_clear_until IS NOT NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM clear_reports
+                    WHERE
+                        clear_reports.game = 'smm2'
+                        AND clear_reports.level_id = levels_smm2.id
+                        AND clear_reports.created_at >= $1
+                )",
+            fetched_at
+        )
+        .execute(executor)
+        .await
+    }
+
+    /// Updates a whole batch of existing levels in a single round-trip. See
+    /// [Self::store_many] for how this works.
+    pub async fn update_many<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        levels: &[Smm2Level],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        let columns = BatchColumns::from(levels);
+        sqlx::query!(
+            "UPDATE levels_smm2 SET
+                year = batch.year,
+                title = batch.title,
+                description = batch.description,
+                uploaded_at = batch.uploaded_at,
+                clearcheck_ms = batch.clearcheck_ms,
+                attempts = batch.attempts,
+                footprints = batch.footprints,
+                likes = batch.likes,
+                boos = batch.boos,
+                comments = batch.comments,
+                clear_condition = batch.clear_condition,
+                clear_condition_magnitude = batch.clear_condition_magnitude,
+                style = batch.style,
+                theme = batch.theme,
+                tags = batch.tags::text[]
+            FROM UNNEST(
+                $1::text[],
+                $2::bigint[],
+                $3::text[],
+                $4::text[],
+                $5::timestamptz[],
+                $6::bigint[],
+                $7::bigint[],
+                $8::bigint[],
+                $9::bigint[],
+                $10::bigint[],
+                $11::bigint[],
+                $12::bigint[],
+                $13::bigint[],
+                $14::text[],
+                $15::text[],
+                $16::text[]
+            ) AS batch (
                 id,
                 year,
                 title,
@@ -111,69 +172,88 @@ impl Smm2Level {
                 style,
                 theme,
                 tags
-            FROM levels_smm2
-            WHERE 1 = 1",
-        );
-
-        push_optional_filter!(query, params.year, " AND year = ");
-        push_optional_filter!(query, params.min_attempts, " AND attempts >= ");
-        push_optional_filter!(query, params.max_attempts, " AND attempts <= ");
-        push_optional_filter!(query, params.min_footprints, " AND footprints >= ");
-        push_optional_filter!(query, params.max_footprints, " AND footprints <= ");
-        push_optional_filter!(query, params.min_clearcheck_ms, " AND clearcheck_ms >= ");
-        push_optional_filter!(query, params.max_clearcheck_ms, " AND clearcheck_ms <= ");
-        push_optional_filter!(query, &params.style, " AND style =  ");
-        push_optional_filter!(query, &params.theme, " AND theme =  ");
-        push_optional_filter!(query, &params.tag, " AND ", " = ANY(tags)");
-
-        if let Some(cc_filter) = &params.clear_condition_group {
-            match cc_filter.id_list() {
-                None => {
-                    query.push(" AND clear_condition IS NULL");
-                }
-                Some(ids) => {
-                    query.push(format!(
-                        " AND clear_condition IN({})",
-                        ids.iter()
-                            .map(|i| i.to_string())
-                            .collect::<Vec<String>>()
-                            .join(",")
-                    ));
-                }
-            }
-        }
-
-        query.push(" ORDER BY random() LIMIT 1");
-
-        query
-            .build_query_as::<Smm2Level>()
-            .fetch_optional(executor)
-            .await
+            )
+            WHERE levels_smm2.id = batch.id",
+            &columns.id,
+            &columns.year,
+            &columns.title,
+            &columns.description as &[Option<String>],
+            &columns.uploaded_at,
+            &columns.clearcheck_ms,
+            &columns.attempts,
+            &columns.footprints,
+            &columns.likes,
+            &columns.boos,
+            &columns.comments,
+            &columns.clear_condition as &[Option<i64>],
+            &columns.clear_condition_magnitude as &[Option<i64>],
+            &columns.style,
+            &columns.theme,
+            &columns.tags,
+        )
+        .execute(executor)
+        .await
     }
 
-    pub async fn id_exists<'a, Executor: PgExecutor<'a>>(
+    pub async fn get_all<'a, Executor: PgExecutor<'a>>(
         executor: Executor,
-        level_id: &str,
-    ) -> bool {
-        sqlx::query!("SELECT id FROM levels_smm2 WHERE id = $1 LIMIT 1", level_id)
-            .fetch_optional(executor)
-            .await
-            .is_ok_and(|r| r.is_some())
+    ) -> Result<Vec<Smm2Level>, sqlx::Error> {
+        sqlx::query_as!(
+            Smm2Level,
+            "SELECT
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags
+            FROM levels_smm2"
+        )
+        .fetch_all(executor)
+        .await
     }
 
-    pub fn normalized_internal_level_id(raw_id: &str) -> String {
-        raw_id.trim().replace('-', "").to_lowercase()
+    /// Returns the levels that are currently hidden by
+    /// [Level::hide_pending_clear], and until when.
+    pub async fn get_pending_clears<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+    ) -> Result<Vec<(String, OffsetDateTime)>, sqlx::Error> {
+        Ok(sqlx::query!(
+            r#"SELECT id, pending_clear_until as "pending_clear_until!"
+            FROM levels_smm2
+            WHERE pending_clear_until > now()"#
+        )
+        .fetch_all(executor)
+        .await?
+        .into_iter()
+        .map(|row| (row.id, row.pending_clear_until))
+        .collect())
     }
 
-    pub fn formatted_level_id(raw_id: &str) -> String {
-        raw_id
-            .to_uppercase()
-            .chars()
-            .collect::<Vec<char>>()
-            .chunks(3)
-            .map(|c| c.iter().collect::<String>())
-            .collect::<Vec<String>>()
-            .join("-")
+    /// Changes whenever levels were imported or archived, so anything caching
+    /// the level table can tell when it's out of date.
+    pub async fn data_version<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+    ) -> Result<DataVersion, sqlx::Error> {
+        sqlx::query_as!(
+            DataVersion,
+            "SELECT
+                (SELECT max(id) FROM import_runs WHERE game = 'smm2' AND outcome = 'committed')
+                    AS last_import_run_id,
+                (SELECT max(archive_id) FROM levels_smm2_archive) AS last_archive_id"
+        )
+        .fetch_one(executor)
+        .await
     }
 
     pub fn clear_condition_text(id: i64, magnitude: Option<i64>) -> String {
@@ -196,6 +276,388 @@ impl Smm2Level {
     }
 }
 
+impl Level for Smm2Level {
+    const GAME: Game = Game::Smm2;
+    type Filter = FilterParams;
+
+    /// Moves the levels with the given IDs from `levels_smm2` into
+    /// `levels_smm2_archive`, keeping their last known data around.
+    async fn archive_many(
+        conn: &mut PgConnection,
+        level_ids: &[String],
+        reason: RemovalReason,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "WITH removed AS (
+                DELETE FROM levels_smm2 WHERE id = ANY($1) RETURNING *
+            )
+            INSERT INTO levels_smm2_archive (
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags,
+                first_seen_at,
+                removal_reason
+            )
+            SELECT
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags,
+                first_seen_at,
+                $2
+            FROM removed",
+            level_ids,
+            reason as RemovalReason,
+        )
+        .execute(conn)
+        .await
+    }
+
+    /// Inserts a whole batch of levels in a single round-trip, by passing
+    /// each column as an array and `UNNEST`ing them on the database side.
+    /// Conflicting IDs are ignored, just like in [Self::store].
+    async fn store_many<'a, Executor: PgExecutor<'a>>(
+        executor: Executor,
+        levels: &[Smm2Level],
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        let columns = BatchColumns::from(levels);
+        sqlx::query!(
+            "INSERT INTO levels_smm2 (
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags
+            )
+            SELECT
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags::text[]
+            FROM UNNEST(
+                $1::text[],
+                $2::bigint[],
+                $3::text[],
+                $4::text[],
+                $5::timestamptz[],
+                $6::bigint[],
+                $7::bigint[],
+                $8::bigint[],
+                $9::bigint[],
+                $10::bigint[],
+                $11::bigint[],
+                $12::bigint[],
+                $13::bigint[],
+                $14::text[],
+                $15::text[],
+                $16::text[]
+            ) AS batch (
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags
+            )
+            ON CONFLICT DO NOTHING",
+            &columns.id,
+            &columns.year,
+            &columns.title,
+            &columns.description as &[Option<String>],
+            &columns.uploaded_at,
+            &columns.clearcheck_ms,
+            &columns.attempts,
+            &columns.footprints,
+            &columns.likes,
+            &columns.boos,
+            &columns.comments,
+            &columns.clear_condition as &[Option<i64>],
+            &columns.clear_condition_magnitude as &[Option<i64>],
+            &columns.style,
+            &columns.theme,
+            &columns.tags,
+        )
+        .execute(executor)
+        .await
+    }
+
+    async fn restore_archived(
+        conn: &mut PgConnection,
+        level_id: &str,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "WITH restored AS (
+                DELETE FROM levels_smm2_archive
+                WHERE
+                    archive_id = (
+                        SELECT archive_id FROM levels_smm2_archive
+                        WHERE id = $1
+                        ORDER BY removed_at DESC, archive_id DESC
+                        LIMIT 1
+                    )
+                    AND removal_reason IN ('verified_cleared', 'manually_removed')
+                    AND NOT EXISTS (SELECT 1 FROM levels_smm2 WHERE id = $1)
+                RETURNING *
+            )
+            INSERT INTO levels_smm2 (
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags,
+                first_seen_at
+            )
+            SELECT
+                id,
+                year,
+                title,
+                description,
+                uploaded_at,
+                clearcheck_ms,
+                attempts,
+                footprints,
+                likes,
+                boos,
+                comments,
+                clear_condition,
+                clear_condition_magnitude,
+                style,
+                theme,
+                tags,
+                first_seen_at
+            FROM restored",
+            level_id
+        )
+        .execute(conn)
+        .await
+    }
+
+    async fn get_random_level(
+        db: &PgPool,
+        params: &FilterParams,
+    ) -> Result<Option<Smm2Level>, sqlx::Error> {
+        random_level_query(
+            "id,
+            year,
+            title,
+            description,
+            uploaded_at,
+            clearcheck_ms,
+            attempts,
+            footprints,
+            likes,
+            boos,
+            comments,
+            clear_condition,
+            clear_condition_magnitude,
+            style,
+            theme,
+            tags",
+            "levels_smm2",
+            |query| params.push_conditions(query),
+        )
+        .build_query_as::<Smm2Level>()
+        .fetch_optional(db)
+        .await
+    }
+
+    async fn count_levels(db: &PgPool, params: &FilterParams) -> Result<i64, sqlx::Error> {
+        level_count_query("levels_smm2", |query| params.push_conditions(query))
+            .build_query_scalar()
+            .fetch_one(db)
+            .await
+    }
+
+    fn pick_from_index(index: &LevelIndex, params: &FilterParams) -> Option<IndexedPick<Self>> {
+        index.pick(params)
+    }
+
+    fn count_in_index(index: &LevelIndex, params: &FilterParams) -> Option<usize> {
+        index.count(params)
+    }
+
+    async fn id_exists(db: &PgPool, level_id: &str) -> bool {
+        sqlx::query!("SELECT id FROM levels_smm2 WHERE id = $1 LIMIT 1", level_id)
+            .fetch_optional(db)
+            .await
+            .is_ok_and(|r| r.is_some())
+    }
+
+    async fn hide_pending_clear(
+        conn: &mut PgConnection,
+        level_id: &str,
+        until: OffsetDateTime,
+    ) -> Result<PgQueryResult, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE levels_smm2 SET pending_clear_until = GREATEST(pending_clear_until, $2) WHERE id = $1",
+            level_id,
+            until
+        )
+        .execute(conn)
+        .await
+    }
+
+    fn formatted_level_id(raw_id: &str) -> String {
+        chunked_level_id(raw_id, 3)
+    }
+
+    fn is_valid_level_id(normalized_id: &str) -> bool {
+        normalized_id.len() == 9
+    }
+
+    /// Happily special-casing the year parameter...
+    /// - If no year is set, let's make a decision for the user and default
+    ///   them to the current focus year. This could be a config, but I'll be
+    ///   adding a changelog entry for the year flip anyway, so this is fine.
+    /// - However, if we always set a year if it's not provided, we have to add
+    ///   a special-case for the UI to indicate "any". I picked -1, because why
+    ///   not, so we have to unset the parameter so internal processing works.
+    fn effective_filters(params: &FilterParams) -> FilterParams {
+        let mut effective_filters = params.clone();
+        effective_filters.year = match effective_filters.year {
+            None => Some(2023),
+            Some(-1) => None,
+            Some(year) => Some(year),
+        };
+        effective_filters
+    }
+}
+
+/// A batch of levels, split up into one array per column, for use with
+/// `UNNEST`. PostgreSQL can't `UNNEST` an array of arrays into rows of arrays,
+/// so the tags are passed as array literals (`{"a","b"}`) that get cast back
+/// into `text[]` inside the query.
+#[derive(Debug, Default)]
+struct BatchColumns {
+    id: Vec<String>,
+    year: Vec<i64>,
+    title: Vec<String>,
+    description: Vec<Option<String>>,
+    uploaded_at: Vec<OffsetDateTime>,
+    clearcheck_ms: Vec<i64>,
+    attempts: Vec<i64>,
+    footprints: Vec<i64>,
+    likes: Vec<i64>,
+    boos: Vec<i64>,
+    comments: Vec<i64>,
+    clear_condition: Vec<Option<i64>>,
+    clear_condition_magnitude: Vec<Option<i64>>,
+    style: Vec<String>,
+    theme: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl From<&[Smm2Level]> for BatchColumns {
+    fn from(levels: &[Smm2Level]) -> Self {
+        let mut columns = Self::default();
+        for level in levels {
+            columns.id.push(level.id.clone());
+            columns.year.push(level.year);
+            columns.title.push(level.title.clone());
+            columns.description.push(level.description.clone());
+            columns.uploaded_at.push(level.uploaded_at);
+            columns.clearcheck_ms.push(level.clearcheck_ms);
+            columns.attempts.push(level.attempts);
+            columns.footprints.push(level.footprints);
+            columns.likes.push(level.likes);
+            columns.boos.push(level.boos);
+            columns.comments.push(level.comments);
+            columns.clear_condition.push(level.clear_condition);
+            columns
+                .clear_condition_magnitude
+                .push(level.clear_condition_magnitude);
+            columns.style.push(level.style.clone());
+            columns.theme.push(level.theme.clone());
+            columns.tags.push(pg_array_literal(&level.tags));
+        }
+        columns
+    }
+}
+
+fn pg_array_literal(values: &[String]) -> String {
+    let quoted = values
+        .iter()
+        .map(|v| format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<String>>();
+    format!("{{{}}}", quoted.join(","))
+}
+
+/// See [Smm2Level::data_version].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataVersion {
+    pub last_import_run_id: Option<i64>,
+    pub last_archive_id: Option<i64>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, sqlx::Type)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "text", rename_all = "UPPERCASE")]
@@ -255,7 +717,18 @@ pub enum ClearConditionGroup {
     Collecting,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Whether levels need to have any or all of the tags in [FilterParams::tag].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMode {
+    #[default]
+    Any,
+    All,
+}
+
+/// The style, theme, tag, and clear condition filters can be repeated to
+/// match any of the values, and excluded with their `exclude_` counterparts.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct FilterParams {
     #[serde(default, deserialize_with = "empty_string_as_none")]
     pub year: Option<i64>,
@@ -278,21 +751,101 @@ pub struct FilterParams {
     #[serde(default, deserialize_with = "empty_string_as_none")]
     pub max_clearcheck_ms: Option<i64>,
 
-    #[serde(default, deserialize_with = "empty_string_as_none_enum")]
-    pub clear_condition_group: Option<ClearConditionGroup>,
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub clear_condition_group: Vec<ClearConditionGroup>,
 
-    #[serde(default, deserialize_with = "empty_string_as_none_enum")]
-    pub style: Option<Style>,
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub exclude_clear_condition_group: Vec<ClearConditionGroup>,
 
-    #[serde(default, deserialize_with = "empty_string_as_none_enum")]
-    pub theme: Option<Theme>,
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub style: Vec<Style>,
+
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub exclude_style: Vec<Style>,
+
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub theme: Vec<Theme>,
+
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub exclude_theme: Vec<Theme>,
+
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub tag: Vec<Tag>,
+
+    #[serde(default, deserialize_with = "empty_strings_skipped_enum")]
+    pub exclude_tag: Vec<Tag>,
 
     #[serde(default, deserialize_with = "empty_string_as_none_enum")]
-    pub tag: Option<Tag>,
+    pub tag_mode: Option<TagMode>,
+}
+
+impl FilterParams {
+    /// Appends an `AND ...` condition for every filter that is set.
+    fn push_conditions<'a>(&'a self, query: &mut QueryBuilder<'a, Postgres>) {
+        push_optional_filter!(query, self.year, " AND year = ");
+        push_optional_filter!(query, self.min_attempts, " AND attempts >= ");
+        push_optional_filter!(query, self.max_attempts, " AND attempts <= ");
+        push_optional_filter!(query, self.min_footprints, " AND footprints >= ");
+        push_optional_filter!(query, self.max_footprints, " AND footprints <= ");
+        push_optional_filter!(query, self.min_clearcheck_ms, " AND clearcheck_ms >= ");
+        push_optional_filter!(query, self.max_clearcheck_ms, " AND clearcheck_ms <= ");
+        push_list_filter!(query, &self.style, " AND style = ANY(", ")");
+        push_list_filter!(query, &self.exclude_style, " AND style <> ALL(", ")");
+        push_list_filter!(query, &self.theme, " AND theme = ANY(", ")");
+        push_list_filter!(query, &self.exclude_theme, " AND theme <> ALL(", ")");
+        match self.tag_mode.unwrap_or_default() {
+            TagMode::Any => {
+                push_list_filter!(query, &self.tag, " AND tags && ");
+            }
+            TagMode::All => {
+                push_list_filter!(query, &self.tag, " AND tags @> ");
+            }
+        }
+        push_list_filter!(query, &self.exclude_tag, " AND NOT tags && ");
+
+        if let Some(condition) = ClearConditionGroup::sql_condition(&self.clear_condition_group) {
+            query.push(format!(" AND {condition}"));
+        }
+        // `IS NOT TRUE`, because `clear_condition IN(...)` is NULL for levels
+        // without a clear condition, and those shouldn't be excluded.
+        if let Some(condition) =
+            ClearConditionGroup::sql_condition(&self.exclude_clear_condition_group)
+        {
+            query.push(format!(" AND {condition} IS NOT TRUE"));
+        }
+    }
 }
 
 impl ClearConditionGroup {
-    fn id_list(&self) -> Option<Vec<i64>> {
+    /// A condition matching levels in any of the `groups`, or `None` if there
+    /// are no groups.
+    fn sql_condition(groups: &[ClearConditionGroup]) -> Option<String> {
+        if groups.is_empty() {
+            return None;
+        }
+
+        let mut conditions = vec![];
+        let mut ids = vec![];
+        for group in groups {
+            match group.id_list() {
+                None => conditions.push("clear_condition IS NULL".to_string()),
+                Some(group_ids) => ids.extend(group_ids),
+            }
+        }
+        if !ids.is_empty() {
+            conditions.push(format!(
+                "clear_condition IN({})",
+                ids.iter()
+                    .map(|i| i.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            ));
+        }
+
+        Some(format!("({})", conditions.join(" OR ")))
+    }
+
+    pub(crate) fn id_list(&self) -> Option<Vec<i64>> {
         match self {
             Self::None => None,
             Self::NoJumping => Some(vec![1]),
@@ -420,10 +973,126 @@ fn clear_condition_label(id: i64) -> Option<&'static str> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
 
     #[test]
     fn formatted_level_id_formats_correctly() {
         assert_eq!(Smm2Level::formatted_level_id("abc123DEF"), "ABC-123-DEF")
     }
+
+    #[test]
+    fn pg_array_literal_quotes_values() {
+        assert_eq!(pg_array_literal(&[]), "{}");
+        assert_eq!(
+            pg_array_literal(&["speedrun".to_owned(), r#"we"ird\"#.to_owned()]),
+            r#"{"speedrun","we\"ird\\"}"#
+        );
+    }
+
+    #[test]
+    fn filter_params_accept_single_and_repeated_values() {
+        // Bookmarked URLs from before filters could be repeated.
+        let params: FilterParams =
+            serde_html_form::from_str("year=2023&style=smw&theme=&tag=speedrun").unwrap();
+        assert!(matches!(params.style[..], [Style::SMW]));
+        assert!(params.theme.is_empty());
+        assert!(matches!(params.tag[..], [Tag::Speedrun]));
+        assert_eq!(params.tag_mode, None);
+
+        let params: FilterParams = serde_html_form::from_str(
+            "style=smw&style=smb3&exclude_tag=music&exclude_tag=art&tag_mode=all",
+        )
+        .unwrap();
+        assert!(matches!(params.style[..], [Style::SMW, Style::SMB3]));
+        assert!(matches!(params.exclude_tag[..], [Tag::Music, Tag::Art]));
+        assert_eq!(params.tag_mode, Some(TagMode::All));
+        assert_eq!(
+            serde_html_form::to_string(&params).unwrap(),
+            "style=smw&style=smb3&exclude_tag=music&exclude_tag=art&tag_mode=all"
+        );
+    }
+
+    #[test]
+    fn clear_condition_groups_combine_into_one_condition() {
+        assert_eq!(ClearConditionGroup::sql_condition(&[]), None);
+        assert_eq!(
+            ClearConditionGroup::sql_condition(&[
+                ClearConditionGroup::None,
+                ClearConditionGroup::NoJumping,
+                ClearConditionGroup::NoDamage,
+            ])
+            .unwrap(),
+            "(clear_condition IS NULL OR clear_condition IN(1,4))"
+        );
+    }
+
+    /// Picks 200 levels per matching level and checks that every one of
+    /// the `matches` levels came up about equally often. The limit is the
+    /// chi-squared value for `matches - 1` degrees of freedom at p = 0.001,
+    /// so this fails by chance once in a thousand runs.
+    async fn assert_picks_uniformly(
+        db: &PgPool,
+        params: &FilterParams,
+        matches: usize,
+        chi_squared_limit: f64,
+    ) -> HashMap<String, usize> {
+        let picks = matches * 200;
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..picks {
+            let level = Smm2Level::get_random_level(db, params)
+                .await
+                .unwrap()
+                .unwrap();
+            *counts.entry(level.id).or_default() += 1;
+        }
+        assert_eq!(counts.len(), matches);
+
+        let expected = picks as f64 / matches as f64;
+        let chi_squared: f64 = counts
+            .values()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi_squared < chi_squared_limit, "{counts:?}");
+
+        counts
+    }
+
+    /// Runs against the placeholder levels from the migrations.
+    #[sqlx::test]
+    async fn get_random_level_picks_uniformly(db: PgPool) {
+        let mut params = FilterParams::default();
+        assert_picks_uniformly(&db, &params, 42, 74.74).await;
+
+        params.year = Some(2023);
+        let counts = assert_picks_uniformly(&db, &params, 16, 37.70).await;
+        for _ in 0..50 {
+            let level = Smm2Level::get_random_level(&db, &params)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(level.year, 2023);
+        }
+
+        let hidden_id = counts.into_keys().next().unwrap();
+        Smm2Level::hide_pending_clear(
+            &mut db.acquire().await.unwrap(),
+            &hidden_id,
+            OffsetDateTime::now_utc() + time::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+        let counts = assert_picks_uniformly(&db, &params, 15, 36.12).await;
+        assert!(!counts.contains_key(&hidden_id));
+        assert_picks_uniformly(&db, &FilterParams::default(), 41, 73.40).await;
+
+        params.style = vec![Style::SMB3];
+        assert!(
+            Smm2Level::get_random_level(&db, &params)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
 }
@@ -3,13 +3,16 @@ use std::sync::Arc;
 use axum::{
     Json,
     extract::{Request, State},
-    http::StatusCode,
+    http::{HeaderValue, StatusCode, header::WWW_AUTHENTICATE},
     response::{Html, IntoResponse, Response},
 };
 use serde_json::json;
 use tracing::error;
 
-use crate::components::app_state::AppState;
+use crate::components::{
+    api_sources::ApiSourceError, app_state::AppState, blocklist::BlocklistError,
+    level_reports::LevelReportError, rate_limiter::RateLimitStatus,
+};
 
 /// Happy little general-purpose response error. This should be used for all the
 /// errors returned by any route handler, because it's also responsible for
@@ -25,6 +28,9 @@ pub enum ResponseError {
     #[error("internal server error")]
     DatabaseError(#[from] sqlx::Error),
 
+    #[error("{0}")]
+    ForbiddenError(String),
+
     #[error("internal server error")]
     InternalError(String),
 
@@ -33,6 +39,34 @@ pub enum ResponseError {
 
     #[error(transparent)]
     TemplateError(#[from] minijinja::Error),
+
+    #[error("too many requests, please slow down")]
+    TooManyRequestsError(RateLimitStatus),
+
+    #[error("unauthorized")]
+    UnauthorizedError(AuthChallenge),
+}
+
+/// Tells a client how to authenticate after a `401`, by way of the
+/// `WWW-Authenticate` header.
+#[derive(Clone, Copy, Debug)]
+pub enum AuthChallenge {
+    /// Basic auth with the admin token, so browsers show a login prompt.
+    Admin,
+    /// A bearer token, as used by API sources.
+    Bearer,
+    /// Nothing the client could do about it, like a bad request signature.
+    None,
+}
+
+impl AuthChallenge {
+    fn header_value(self) -> Option<HeaderValue> {
+        match self {
+            Self::Admin => Some(HeaderValue::from_static("Basic realm=\"smm-zerop admin\"")),
+            Self::Bearer => Some(HeaderValue::from_static("Bearer realm=\"smm-zerop api\"")),
+            Self::None => None,
+        }
+    }
 }
 
 impl ResponseError {
@@ -41,7 +75,10 @@ impl ResponseError {
     /// care about generic 404s.
     fn maybe_log(&self) {
         match self {
-            Self::NotFoundError() => {}
+            Self::ForbiddenError(_)
+            | Self::NotFoundError()
+            | Self::TooManyRequestsError(_)
+            | Self::UnauthorizedError(_) => {}
             _ => {
                 error!("response error: {:?}", self);
             }
@@ -53,20 +90,41 @@ impl ResponseError {
     fn status_code(&self) -> StatusCode {
         match self {
             Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::ForbiddenError(_) => StatusCode::FORBIDDEN,
             Self::NotFoundError() => StatusCode::NOT_FOUND,
+            Self::TooManyRequestsError(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::UnauthorizedError(_) => StatusCode::UNAUTHORIZED,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
+    /// Some errors need extra headers to be useful, no matter how they're
+    /// rendered. Browsers only show a login prompt for a 401 if there's a
+    /// `WWW-Authenticate` header, for example.
+    fn with_headers(&self, mut response: Response) -> Response {
+        match self {
+            Self::UnauthorizedError(challenge) => {
+                if let Some(value) = challenge.header_value() {
+                    response.headers_mut().insert(WWW_AUTHENTICATE, value);
+                }
+            }
+            Self::TooManyRequestsError(status) => status.insert_headers(response.headers_mut()),
+            _ => {}
+        }
+        response
+    }
+
     /// Renders the error as a plain text response. This is a fallback in case
     /// other methods fail, or if the client does not send an Accept header.
     fn plain_text_response(&self) -> Response {
         self.maybe_log();
-        (
-            self.status_code(),
-            format!("{}: {}", self.status_code().as_u16(), self),
+        self.with_headers(
+            (
+                self.status_code(),
+                format!("{}: {}", self.status_code().as_u16(), self),
+            )
+                .into_response(),
         )
-            .into_response()
     }
 
     /// Renders the error as a JSON. The JSON has two fields: `code`, containing
@@ -79,7 +137,7 @@ impl ResponseError {
             "message": self.to_string()
         });
 
-        (self.status_code(), Json(payload)).into_response()
+        self.with_headers((self.status_code(), Json(payload)).into_response())
     }
 
     /// Renders a fancy HTML error message. As opposed to the other renderers,
@@ -88,20 +146,22 @@ impl ResponseError {
     /// for multiple reasons.
     fn html_response(&self, app_state: &AppState) -> Result<Response, ResponseError> {
         self.maybe_log();
-        Ok((
-            self.status_code(),
-            Html(
-                app_state
-                    .template
-                    .acquire_env()
-                    .get_template("fallback/error.html")?
-                    .render(minijinja::context! {
-                        status_code => self.status_code().as_u16(),
-                        message => self.to_string()
-                    })?,
-            ),
-        )
-            .into_response())
+        Ok(self.with_headers(
+            (
+                self.status_code(),
+                Html(
+                    app_state
+                        .template
+                        .acquire_env()
+                        .get_template("fallback/error.html")?
+                        .render(minijinja::context! {
+                            status_code => self.status_code().as_u16(),
+                            message => self.to_string()
+                        })?,
+                ),
+            )
+                .into_response(),
+        ))
     }
 
     /// Middleware function used to render content-type appropriate errors and
@@ -152,3 +212,41 @@ impl IntoResponse for ResponseError {
         response
     }
 }
+
+impl From<ApiSourceError> for ResponseError {
+    fn from(err: ApiSourceError) -> Self {
+        match err {
+            ApiSourceError::InvalidToken | ApiSourceError::TokenRequired => {
+                Self::UnauthorizedError(AuthChallenge::Bearer)
+            }
+            ApiSourceError::Disabled(_) | ApiSourceError::SourceMismatch(_) => {
+                Self::ForbiddenError(err.to_string())
+            }
+            ApiSourceError::DatabaseError(err) => Self::DatabaseError(err),
+        }
+    }
+}
+
+impl From<BlocklistError> for ResponseError {
+    fn from(err: BlocklistError) -> Self {
+        match err {
+            BlocklistError::InvalidLevelId(_) => Self::BadRequest(err.to_string()),
+            BlocklistError::DatabaseError(err) => Self::DatabaseError(err),
+        }
+    }
+}
+
+impl From<LevelReportError> for ResponseError {
+    fn from(err: LevelReportError) -> Self {
+        match err {
+            LevelReportError::InvalidLevelId(_) | LevelReportError::NoteTooLong => {
+                Self::BadRequest(err.to_string())
+            }
+            LevelReportError::UnknownLevel(_) | LevelReportError::NoOpenReports(_) => {
+                Self::NotFoundError()
+            }
+            LevelReportError::BlocklistError(err) => err.into(),
+            LevelReportError::DatabaseError(err) => Self::DatabaseError(err),
+        }
+    }
+}
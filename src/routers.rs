@@ -1,10 +1,15 @@
+mod admin;
 mod app_meta;
+mod discord;
+mod levels;
 mod smm2;
 mod static_pages;
 
+use std::net::{IpAddr, SocketAddr};
+
 use axum::{
     Router,
-    extract::Request,
+    extract::{ConnectInfo, Request, State},
     handler::Handler,
     http::{HeaderValue, header::CACHE_CONTROL},
     middleware,
@@ -13,7 +18,14 @@ use axum::{
 use tower::ServiceBuilder;
 use tower_http::services::ServeDir;
 
-use crate::{components::app_state::AppState, errors::ResponseError};
+use crate::{
+    components::{
+        api_sources,
+        app_state::AppState,
+        rate_limiter::{RateLimitClass, RateLimitKey, RateLimitStatus},
+    },
+    errors::ResponseError,
+};
 
 /// Builds the main router.
 /// This should collect all the routes from all over the app, and return a
@@ -39,14 +51,111 @@ pub fn build_main_router(state: AppState) -> Router {
         );
 
     Router::new()
+        .merge(admin::build(state.clone()))
         .merge(app_meta::build())
-        .merge(smm2::build())
+        .merge(discord::build())
+        .merge(levels::build(state.clone()))
+        .merge(smm2::build(state.clone()))
         .merge(static_pages::build())
         .layer(error_handling_layer)
         .fallback_service(fallback_service)
         .with_state(state)
 }
 
+/// Middleware that counts requests against the
+/// [RateLimiter](crate::components::rate_limiter::RateLimiter) per client IP
+/// address, and rejects them with a `429` if the client's bucket is empty.
+/// All responses get the `RateLimit-*` headers, so well-behaved clients can
+/// slow down before they're limited.
+pub async fn rate_limit_middleware(
+    State((app_state, class)): State<(AppState, RateLimitClass)>,
+    req: Request,
+    next: middleware::Next,
+) -> Result<Response, ResponseError> {
+    let status = check_rate_limit(&app_state, class, client_ip_key(&app_state, &req))?;
+    Ok(with_rate_limit_headers(next.run(req).await, status))
+}
+
+/// Like [rate_limit_middleware], but for routes that accept API source
+/// tokens. Requests with the token of an enabled API source are counted
+/// against that source instead of the client's IP address. Tokens the rate
+/// limiter doesn't know yet are counted against the IP address first, so
+/// nobody can make throttled requests cost a database lookup by sending
+/// made-up tokens.
+pub async fn api_source_rate_limit_middleware(
+    State((app_state, class)): State<(AppState, RateLimitClass)>,
+    mut req: Request,
+    next: middleware::Next,
+) -> Result<Response, ResponseError> {
+    let token_hash = api_sources::bearer_token_hash(req.headers());
+    let known_source = token_hash
+        .as_deref()
+        .and_then(|token_hash| app_state.rate_limiter.api_source_for_token(token_hash));
+
+    let status = if let Some(source_key) = known_source {
+        check_rate_limit(&app_state, class, RateLimitKey::ApiSource(source_key))?
+    } else {
+        let status = check_rate_limit(&app_state, class, client_ip_key(&app_state, &req))?;
+        if let Some(token_hash) = token_hash {
+            let token_owner = api_sources::identify(&app_state.database, req.headers()).await?;
+            if let Some(api_source) = &token_owner.0
+                && api_source.enabled
+            {
+                app_state
+                    .rate_limiter
+                    .remember_api_source_token(token_hash, api_source.key.clone());
+            }
+            req.extensions_mut().insert(token_owner);
+        }
+        status
+    };
+
+    Ok(with_rate_limit_headers(next.run(req).await, status))
+}
+
+fn check_rate_limit(
+    app_state: &AppState,
+    class: RateLimitClass,
+    key: RateLimitKey,
+) -> Result<Option<RateLimitStatus>, ResponseError> {
+    app_state
+        .rate_limiter
+        .check(class, key)
+        .map_err(ResponseError::TooManyRequestsError)
+}
+
+fn with_rate_limit_headers(mut response: Response, status: Option<RateLimitStatus>) -> Response {
+    if let Some(status) = status {
+        status.insert_headers(response.headers_mut());
+    }
+    response
+}
+
+fn client_ip_key(app_state: &AppState, req: &Request) -> RateLimitKey {
+    client_ip(app_state, req).map_or(RateLimitKey::Unknown, RateLimitKey::Ip)
+}
+
+/// Figures out the client's IP address, either from the configured proxy
+/// header, or from the connection itself. If the header is missing or broken,
+/// the connection is used as well, so those clients don't all end up sharing
+/// one bucket.
+fn client_ip(app_state: &AppState, req: &Request) -> Option<IpAddr> {
+    if let Some(header_name) = &app_state.settings.rate_limit_client_ip_header
+        && let Some(ip) = req
+            .headers()
+            .get(header_name)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok())
+    {
+        return Some(ip);
+    }
+
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
 /// Little middleware that sets the Cache-Control header for static files served
 /// from the public/ directory.
 /// If a Cache header is already set for some reason, it does nothing.
@@ -0,0 +1,338 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{
+        HeaderMap, Method, StatusCode,
+        header::{AUTHORIZATION, ORIGIN, REFERER},
+    },
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use minijinja::context;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    components::{app_state::AppState, blocklist, level_reports},
+    entities::{
+        api_source::ApiSource,
+        blocklist_entry::BlocklistEntry,
+        clear_report::{ClearReport, DeliveryStatus},
+        level::Game,
+        level_report::ReportedLevel,
+    },
+    errors::{AuthChallenge, ResponseError},
+};
+
+/// Builds the admin router, with both the admin API and the moderator pages.
+/// Everything in here requires the `ADMIN_API_TOKEN`, see
+/// [require_admin_token].
+pub fn build(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/admin/blocklist",
+            get(list_blocklist).post(add_to_blocklist),
+        )
+        .route(
+            "/api/admin/blocklist/{game}/{level_id}",
+            delete(remove_from_blocklist),
+        )
+        .route(
+            "/api/admin/clear_reports/dead_letters",
+            get(list_dead_lettered_clear_reports),
+        )
+        .route(
+            "/api/admin/clear_reports/withheld",
+            get(list_withheld_clear_reports),
+        )
+        .route(
+            "/api/admin/clear_reports/{id}/requeue",
+            post(requeue_clear_report),
+        )
+        .route("/api/admin/sources", get(list_api_sources))
+        .route("/api/admin/sources/{key}/{action}", post(toggle_api_source))
+        .route("/api/admin/reports", get(api_list_reports))
+        .route(
+            "/api/admin/reports/{game}/{level_id}/{action}",
+            post(api_moderate_reports),
+        )
+        .route("/admin/reports/", get(list_reports))
+        .route(
+            "/admin/reports/{game}/{level_id}/{action}",
+            post(moderate_reports),
+        )
+        .route_layer(middleware::from_fn_with_state(state, require_admin_token))
+}
+
+/// Rejects all requests that don't come with the configured admin token. API
+/// clients send it as a bearer token. For the moderator pages, browsers can
+/// use basic auth with the token as the password, and any username. If no
+/// token is configured, the admin routes just don't exist.
+///
+/// Browsers send cached basic auth credentials along with requests that other
+/// sites trigger, so anything that changes data also has to come from our own
+/// origin, see [is_same_origin].
+async fn require_admin_token(
+    State(app_state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ResponseError> {
+    let Some(admin_token) = app_state.settings.admin_api_token.as_deref() else {
+        return Err(ResponseError::NotFoundError());
+    };
+
+    if !matches!(*req.method(), Method::GET | Method::HEAD)
+        && !is_same_origin(req.headers(), &app_state.settings.public_url)
+    {
+        return Err(ResponseError::ForbiddenError(
+            "cross-origin requests are not allowed".to_owned(),
+        ));
+    }
+
+    let provided_token = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(token_from_authorization);
+
+    match provided_token.as_deref() {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => {
+            Ok(next.run(req).await)
+        }
+        _ => Err(ResponseError::UnauthorizedError(AuthChallenge::Admin)),
+    }
+}
+
+/// Extracts the token from either a `Bearer` or a `Basic` authorization
+/// header. For basic auth, the password is the token.
+fn token_from_authorization(header: &str) -> Option<String> {
+    if let Some(token) = header.strip_prefix("Bearer ") {
+        return Some(token.to_owned());
+    }
+
+    let credentials = BASE64_STANDARD
+        .decode(header.strip_prefix("Basic ")?)
+        .ok()
+        .and_then(|c| String::from_utf8(c).ok())?;
+    credentials
+        .split_once(':')
+        .map(|(_, password)| password.to_owned())
+}
+
+/// Checks that a request was sent from a page on `public_url`, based on the
+/// `Origin` header, or the `Referer` if there is no `Origin`. Requests with
+/// neither don't come from a browser, so there are no cached credentials to
+/// abuse, and they're let through.
+fn is_same_origin(headers: &HeaderMap, public_url: &reqwest::Url) -> bool {
+    let expected_origin = public_url.origin();
+
+    if let Some(origin) = headers.get(ORIGIN) {
+        return origin
+            .to_str()
+            .ok()
+            .and_then(|o| reqwest::Url::parse(o).ok())
+            .is_some_and(|o| o.origin() == expected_origin);
+    }
+
+    match headers.get(REFERER) {
+        Some(referer) => referer
+            .to_str()
+            .ok()
+            .and_then(|r| reqwest::Url::parse(r).ok())
+            .is_some_and(|r| r.origin() == expected_origin),
+        None => true,
+    }
+}
+
+/// Compares two byte strings without bailing out at the first difference, so
+/// the response time doesn't leak how much of a token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Deserialize)]
+struct ListBlocklistParams {
+    game: Option<Game>,
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn list_blocklist(
+    Query(params): Query<ListBlocklistParams>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let entries = BlocklistEntry::get_all(&app_state.database, params.game).await?;
+    Ok(Json(json!({ "entries": entries })))
+}
+
+#[derive(Debug, Deserialize)]
+struct AddToBlocklistPayload {
+    game: Game,
+    level_id: String,
+    reason: Option<String>,
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn add_to_blocklist(
+    State(app_state): State<AppState>,
+    Json(payload): Json<AddToBlocklistPayload>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let entry = blocklist::add(
+        &app_state.database,
+        payload.game,
+        &payload.level_id,
+        payload.reason.as_deref(),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn remove_from_blocklist(
+    Path((game, level_id)): Path<(Game, String)>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    if blocklist::remove(&app_state.database, game, &level_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ResponseError::NotFoundError())
+    }
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn list_dead_lettered_clear_reports(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let clear_reports =
+        ClearReport::get_by_delivery_status(&app_state.database, DeliveryStatus::DeadLettered)
+            .await?;
+    Ok(Json(json!({ "clear_reports": clear_reports })))
+}
+
+/// Reports the level-data API contradicted. Its clear counts lag behind, so
+/// some of these are real clears that should be requeued.
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn list_withheld_clear_reports(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let clear_reports =
+        ClearReport::get_by_delivery_status(&app_state.database, DeliveryStatus::Withheld).await?;
+    Ok(Json(json!({ "clear_reports": clear_reports })))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn requeue_clear_report(
+    Path(id): Path<i64>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    if ClearReport::requeue(&app_state.database, id).await? {
+        app_state.clear_report_wakeup.notify_one();
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ResponseError::NotFoundError())
+    }
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn list_api_sources(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let sources = ApiSource::get_all(&app_state.database).await?;
+    Ok(Json(json!({ "sources": sources })))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ToggleAction {
+    Enable,
+    Disable,
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn toggle_api_source(
+    Path((key, action)): Path<(String, ToggleAction)>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let enabled = matches!(action, ToggleAction::Enable);
+    if ApiSource::set_enabled(&app_state.database, &key, enabled).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ResponseError::NotFoundError())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ModerationAction {
+    Approve,
+    Reject,
+}
+
+async fn apply_moderation_action(
+    app_state: &AppState,
+    game: Game,
+    level_id: &str,
+    action: ModerationAction,
+) -> Result<u64, ResponseError> {
+    Ok(match action {
+        ModerationAction::Approve => {
+            level_reports::approve(&app_state.database, game, level_id).await?
+        }
+        ModerationAction::Reject => {
+            level_reports::reject(&app_state.database, game, level_id).await?
+        }
+    })
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn api_list_reports(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let reported_levels = ReportedLevel::get_open(&app_state.database, None, None).await?;
+    Ok(Json(json!({ "reported_levels": reported_levels })))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn api_moderate_reports(
+    Path((game, level_id, action)): Path<(Game, String, ModerationAction)>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let resolved = apply_moderation_action(&app_state, game, &level_id, action).await?;
+    Ok(Json(json!({ "resolved": resolved })))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn list_reports(State(app_state): State<AppState>) -> Result<Response, ResponseError> {
+    let reported_levels = ReportedLevel::get_open(&app_state.database, None, None).await?;
+    Ok(Html(
+        app_state
+            .template
+            .acquire_env()
+            .get_template("admin/reports.html")?
+            .render(context! { reported_levels })?,
+    )
+    .into_response())
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn moderate_reports(
+    Path((game, level_id, action)): Path<(Game, String, ModerationAction)>,
+    State(app_state): State<AppState>,
+) -> Result<Response, ResponseError> {
+    apply_moderation_action(&app_state, game, &level_id, action).await?;
+    Ok(Redirect::to("/admin/reports/").into_response())
+}
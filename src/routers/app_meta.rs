@@ -1,7 +1,9 @@
 use axum::{Json, Router, extract::State, http::StatusCode, response::IntoResponse, routing::get};
 use serde_json::json;
 
-use crate::{components::app_state::AppState, errors::ResponseError};
+use crate::{
+    components::app_state::AppState, entities::import_run::ImportRun, errors::ResponseError,
+};
 
 /// Builds the fallback router.
 pub fn build() -> Router<AppState> {
@@ -9,6 +11,7 @@ pub fn build() -> Router<AppState> {
         .route("/livez", get(livez_handler))
         .route("/readyz", get(readyz_handler))
         .route("/versionz", get(versionz_handler))
+        .route("/importz", get(importz_handler))
 }
 
 /// `/livez` handler that always returns a 200
@@ -45,3 +48,14 @@ async fn versionz_handler() -> impl IntoResponse {
         "version": env!("CARGO_PKG_VERSION"),
     }))
 }
+
+/// `/importz` handler that returns a JSON object containing the latest import
+/// runs, newest first. Useful to figure out how fresh the level data is.
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn importz_handler(
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let runs = ImportRun::get_latest(&app_state.database, 10).await?;
+    Ok(Json(json!({ "runs": runs })))
+}
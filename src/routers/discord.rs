@@ -0,0 +1,60 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+
+use crate::{
+    components::{
+        app_state::AppState,
+        clear_reports,
+        discord_interactions::{self, Interaction},
+    },
+    errors::{AuthChallenge, ResponseError},
+};
+
+pub fn build() -> Router<AppState> {
+    Router::new().route("/api/discord/interactions", post(interactions))
+}
+
+/// Discord's "Interactions Endpoint URL". Every request has to be signed
+/// with the application's key, so the body is only parsed after the
+/// signature checks out.
+#[axum::debug_handler]
+#[tracing::instrument(skip_all)]
+async fn interactions(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ResponseError> {
+    let Some(public_key) = app_state.settings.discord_public_key.as_deref() else {
+        return Err(ResponseError::NotFoundError());
+    };
+    let public_key = discord_interactions::parse_public_key(public_key)
+        .ok_or_else(|| ResponseError::InternalError("invalid DISCORD_PUBLIC_KEY".to_string()))?;
+
+    let header = |name| headers.get(name).and_then(|h| h.to_str().ok());
+    let (Some(timestamp), Some(signature)) = (
+        header("x-signature-timestamp"),
+        header("x-signature-ed25519"),
+    ) else {
+        return Err(ResponseError::UnauthorizedError(AuthChallenge::None));
+    };
+    if !discord_interactions::verify_signature(&public_key, timestamp, &body, signature) {
+        return Err(ResponseError::UnauthorizedError(AuthChallenge::None));
+    }
+
+    let interaction: Interaction = serde_json::from_slice(&body)
+        .map_err(|e| ResponseError::BadRequest(format!("invalid interaction: {e}")))?;
+    let response = discord_interactions::handle(
+        &app_state,
+        &interaction,
+        &clear_reports::client_hints(&headers),
+    )
+    .await;
+
+    Ok(Json(response).into_response())
+}
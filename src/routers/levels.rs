@@ -0,0 +1,242 @@
+use axum::{
+    Form, Json, Router,
+    extract::{Path, RawQuery, State},
+    http::StatusCode,
+    middleware,
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{get, post},
+};
+use minijinja::context;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tower_http::cors::{self, CorsLayer};
+
+use crate::{
+    components::{
+        app_state::AppState, level_index::pick_random_level, level_reports,
+        rate_limiter::RateLimitClass,
+    },
+    entities::{
+        level::{Game, Level},
+        level_report::ReportCategory,
+        smm1_level::Smm1Level,
+        smm2_level::Smm2Level,
+    },
+    errors::ResponseError,
+    routers::rate_limit_middleware,
+};
+
+/// Builds the game-generic level routes. Every game with a [Level]
+/// implementation gets its random level page and API for free.
+pub fn build(state: AppState) -> Router<AppState> {
+    let rate_limited =
+        |class| middleware::from_fn_with_state((state.clone(), class), rate_limit_middleware);
+    let cors_layer = CorsLayer::new()
+        .allow_headers(cors::Any)
+        .allow_methods(cors::Any)
+        .allow_origin(cors::Any);
+    let api_router = Router::new()
+        .route(
+            "/api/{game}/random_level",
+            get(api_random_level).layer(rate_limited(RateLimitClass::RandomLevels)),
+        )
+        .route(
+            "/api/{game}/level_count",
+            get(api_level_count).layer(rate_limited(RateLimitClass::RandomLevels)),
+        )
+        .route(
+            "/api/{game}/report_level",
+            post(api_report_level).layer(rate_limited(RateLimitClass::Reports)),
+        )
+        .layer(cors_layer);
+
+    Router::new()
+        .route(
+            "/{game}/random_level/",
+            get(random_level).layer(rate_limited(RateLimitClass::RandomLevels)),
+        )
+        .route(
+            "/{game}/report_level/",
+            post(report_level).layer(rate_limited(RateLimitClass::Reports)),
+        )
+        .merge(api_router)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ExtraRandomLevelParams {
+    #[serde(default)]
+    mark_clear_success: bool,
+
+    #[serde(default)]
+    report_success: bool,
+}
+
+/// Unknown games are just pages that don't exist, so they should be a 404
+/// instead of a "bad request".
+fn parse_game(raw_game: &str) -> Result<Game, ResponseError> {
+    raw_game.parse().map_err(|_| ResponseError::NotFoundError())
+}
+
+/// Filters can be repeated (`?style=smw&style=smb3`), which `serde_urlencoded`
+/// doesn't support, so this uses `serde_html_form`.
+fn parse_query<T: DeserializeOwned>(query: Option<&str>) -> Result<T, ResponseError> {
+    serde_html_form::from_str(query.unwrap_or_default())
+        .map_err(|e| ResponseError::BadRequest(e.to_string()))
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn random_level(
+    Path(game): Path<String>,
+    RawQuery(query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Response, ResponseError> {
+    match parse_game(&game)? {
+        Game::Smm1 => render_random_level::<Smm1Level>(query.as_deref(), &app_state).await,
+        Game::Smm2 => render_random_level::<Smm2Level>(query.as_deref(), &app_state).await,
+    }
+}
+
+async fn render_random_level<L: Level>(
+    query: Option<&str>,
+    app_state: &AppState,
+) -> Result<Response, ResponseError> {
+    let filter_params: L::Filter = parse_query(query)?;
+    let extra_params: ExtraRandomLevelParams = parse_query(query)?;
+    let effective_filters = L::effective_filters(&filter_params);
+
+    let current_filter_query = serde_html_form::to_string(filter_params)
+        .map_err(|e| ResponseError::InternalError(e.to_string()))?;
+
+    let (level, match_count) = pick_random_level::<L>(app_state, &effective_filters).await?;
+    Ok(Html(
+        app_state
+            .template
+            .acquire_env()
+            .get_template(&format!("{}/random_level.html", L::GAME))?
+            .render(context! {
+                current_filter_query,
+                effective_filters,
+                extra_params,
+                level,
+                match_count
+            })?,
+    )
+    .into_response())
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn api_random_level(
+    Path(game): Path<String>,
+    RawQuery(query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Response, ResponseError> {
+    match parse_game(&game)? {
+        Game::Smm1 => json_random_level::<Smm1Level>(query.as_deref(), &app_state).await,
+        Game::Smm2 => json_random_level::<Smm2Level>(query.as_deref(), &app_state).await,
+    }
+}
+
+async fn json_random_level<L: Level>(
+    query: Option<&str>,
+    app_state: &AppState,
+) -> Result<Response, ResponseError> {
+    let params: L::Filter = parse_query(query)?;
+    let (random_level_result, _) = pick_random_level::<L>(app_state, &params).await?;
+
+    if let Some(result) = random_level_result {
+        Ok(Json(result).into_response())
+    } else {
+        Err(ResponseError::NotFoundError())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LevelCount {
+    count: usize,
+}
+
+/// Counts the levels the random level page would pick from for the given
+/// filters. Unlike `/api/{game}/random_level`, this applies the same defaults
+/// as the page, so the UI can show the count while the filters are changed.
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn api_level_count(
+    Path(game): Path<String>,
+    RawQuery(query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Response, ResponseError> {
+    match parse_game(&game)? {
+        Game::Smm1 => json_level_count::<Smm1Level>(query.as_deref(), &app_state).await,
+        Game::Smm2 => json_level_count::<Smm2Level>(query.as_deref(), &app_state).await,
+    }
+}
+
+async fn json_level_count<L: Level>(
+    query: Option<&str>,
+    app_state: &AppState,
+) -> Result<Response, ResponseError> {
+    let params = L::effective_filters(&parse_query(query)?);
+    let count = match L::count_in_index(&app_state.level_index, &params) {
+        Some(count) => count,
+        None => L::count_levels(&app_state.database, &params).await? as usize,
+    };
+
+    Ok(Json(LevelCount { count }).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportLevelPayload {
+    current_filter_query: Option<String>,
+    level_id: String,
+    category: ReportCategory,
+    note: Option<String>,
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn report_level(
+    Path(game): Path<String>,
+    State(app_state): State<AppState>,
+    Form(payload): Form<ReportLevelPayload>,
+) -> Result<Response, ResponseError> {
+    let game = parse_game(&game)?;
+    level_reports::report(
+        &app_state.database,
+        game,
+        &payload.level_id,
+        payload.category,
+        payload.note.as_deref(),
+    )
+    .await?;
+
+    let mut query: Vec<(String, String)> =
+        serde_html_form::from_str(payload.current_filter_query.as_deref().unwrap_or_default())
+            .map_err(|_| ResponseError::BadRequest("invalid current_filter_query".to_string()))?;
+    query.push(("report_success".to_string(), "true".to_string()));
+
+    Ok(Redirect::to(&format!(
+        "/{game}/random_level/?{}",
+        serde_urlencoded::to_string(query).expect("query params to never be invalid at this point")
+    ))
+    .into_response())
+}
+
+#[axum::debug_handler]
+#[tracing::instrument(skip(app_state))]
+async fn api_report_level(
+    Path(game): Path<String>,
+    State(app_state): State<AppState>,
+    Json(payload): Json<ReportLevelPayload>,
+) -> Result<Response, ResponseError> {
+    let report = level_reports::report(
+        &app_state.database,
+        parse_game(&game)?,
+        &payload.level_id,
+        payload.category,
+        payload.note.as_deref(),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(report)).into_response())
+}
@@ -17,7 +17,6 @@ pub fn build() -> Router<AppState> {
         .route("/", get(index))
         .route("/about/", get(about))
         .route("/changelog/", get(changelog))
-        .route("/smm1/random_level/", get(smm1_random_level))
 }
 
 /// This is just a happy little macro to make rendering completely static pages
@@ -44,4 +43,3 @@ macro_rules! static_page {
 static_page!(index, "index");
 static_page!(about, "about");
 static_page!(changelog, "changelog");
-static_page!(smm1_random_level, "smm1_random_level");